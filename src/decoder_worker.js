@@ -0,0 +1,12 @@
+// Requests that arrive while the module loads are handled once
+// `run_decoder_worker` has replaced this handler.
+const pending = [];
+self.onmessage = event => pending.push(event);
+
+import('../pkg')
+    .then(pkg => {
+        pkg.run_decoder_worker();
+        pending.forEach(event => self.onmessage(event));
+    })
+    // Lets the page decode assets itself instead of waiting forever.
+    .catch(error => self.postMessage({ failed: String(error) }));
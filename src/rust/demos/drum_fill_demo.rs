@@ -1,17 +1,26 @@
 mod beat_display;
 
-use crate::AppRoute;
+use super::{Demo, DemoProps, Feature};
+use crate::{
+    utils::{
+        fade_out,
+        manifest::{AssetKind, Bindings},
+        teardown, AssetBatch, AssetHandles, AudioError, AudioSession, LoadSettings, LoadedAssets,
+        ResampleQuality,
+    },
+    AppRoute,
+};
 use beat_display::BeatDisplay;
 use kira::{
     group::{handle::GroupHandle, GroupSet},
-    manager::AudioManager,
-    metronome::{handle::MetronomeHandle, MetronomeSettings},
+    instance::StopInstanceSettings,
+    metronome::handle::MetronomeHandle,
     sequence::{
         handle::SequenceInstanceHandle, Sequence, SequenceInstanceSettings, SequenceSettings,
     },
-    sound::{handle::SoundHandle, Sound, SoundSettings},
-    Duration, Frame, Tempo,
+    Duration, Tempo,
 };
+use serde::{Deserialize, Serialize};
 use yew::{
     prelude::*,
     services::{interval::IntervalTask, IntervalService},
@@ -28,6 +37,22 @@ When the drum fill is triggered, a second sequence waits \
 for the right beat, stops the previous sequence, starts \
 the drum fill, and then starts a new loop.";
 
+/// The loudness the loudest of the loop and the fills is brought to, in
+/// LUFS. They all get the same gain, so that switching to a fill keeps
+/// the level it was mixed at next to the loop.
+const TARGET_LOUDNESS: f64 = -16.0;
+
+const MANIFEST_URL: &str = "/drum-fill-demo/manifest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Asset {
+    Loop,
+    TwoBeatFill,
+    ThreeBeatFill,
+    FourBeatFill,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum DrumFill {
     TwoBeat,
@@ -121,63 +146,108 @@ impl PlaybackState {
 pub struct DrumFillDemo {
     link: ComponentLink<Self>,
 
-    loop_sound: Option<SoundHandle>,
-    fill_2b: Option<SoundHandle>,
-    fill_3b: Option<SoundHandle>,
-    fill_4b: Option<SoundHandle>,
+    batch: AssetBatch<Asset>,
+    assets: Option<AssetHandles<Asset>>,
 
-    manager: AudioManager,
-    metronome: MetronomeHandle,
-    group: GroupHandle,
+    mixer: Result<Mixer, AudioError>,
+    /// The drum samples, kept until the metronome and group they play
+    /// with are added to the audio manager.
+    loaded: Option<LoadedAssets<Asset>>,
     beat_tracker: Option<SequenceInstanceHandle<Beat>>,
     loop_sequence: Option<SequenceInstanceHandle<DrumFillEvent>>,
 
-    loaded: bool,
     playback_state: PlaybackState,
 
     _interval_service: IntervalTask,
 }
 
 pub enum Message {
-    LoadedLoop(u32, Vec<Frame>),
-    LoadedFill2b(u32, Vec<Frame>),
-    LoadedFill3b(u32, Vec<Frame>),
-    LoadedFill4b(u32, Vec<Frame>),
+    Loading,
+    Loaded(LoadedAssets<Asset>),
 
     PlayClick,
     PlayFillClick,
 
     PopEvents,
+    RetryAudio,
+    /// Stops the loop and any fill, for the transport bar.
+    StopEverything,
+}
+
+/// What the demo adds to the shared audio manager, besides its assets.
+struct Mixer {
+    audio: AudioSession,
+    metronome: MetronomeHandle,
+    group: GroupHandle,
+}
+
+impl Mixer {
+    fn new() -> Result<Self, AudioError> {
+        let audio = AudioSession::start()?;
+        let mut manager = audio.manager();
+        let metronome = manager
+            .add_metronome(Default::default())
+            .map_err(AudioError::rejected)?;
+        let group = match manager.add_group(Default::default()) {
+            Ok(group) => group,
+            Err(error) => {
+                manager.remove_metronome(metronome.id()).ok();
+                return Err(AudioError::rejected(error));
+            }
+        };
+        drop(manager);
+
+        Ok(Self {
+            audio,
+            metronome,
+            group,
+        })
+    }
+}
+
+impl Demo for DrumFillDemo {
+    const TITLE: &'static str = "Drum fill demo";
+    const SLUG: &'static str = "drum-fill-demo";
+    const DESCRIPTION: &'static str =
+        "Sequences keep track of the beat to queue drum fills of the right length.";
+
+    const FEATURES: &'static [Feature] = &[Feature::Metronome, Feature::Sequences, Feature::Groups];
+    const TAGS: &'static [&'static str] = &["rhythm", "timing", "drums", "events"];
+    const MANIFEST_URL: &'static str = MANIFEST_URL;
+
+    type Asset = Asset;
+    const ASSETS: &'static [(Asset, AssetKind)] = &[
+        (Asset::Loop, AssetKind::Sound),
+        (Asset::TwoBeatFill, AssetKind::Sound),
+        (Asset::ThreeBeatFill, AssetKind::Sound),
+        (Asset::FourBeatFill, AssetKind::Sound),
+    ];
 }
 
 impl Component for DrumFillDemo {
     type Message = Message;
 
-    type Properties = ();
+    type Properties = DemoProps;
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/drum-fill-demo/loop.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedLoop(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/drum-fill-demo/2-beat-fill.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedFill2b(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/drum-fill-demo/3-beat-fill.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedFill3b(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/drum-fill-demo/4-beat-fill.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedFill4b(rate, frames))
-        });
-
-        let mut manager = AudioManager::new(Default::default()).unwrap();
-        let metronome = manager
-            .add_metronome(MetronomeSettings::new().tempo(Tempo(128.0)))
-            .unwrap();
-        let group = manager.add_group(Default::default()).unwrap();
+        // Start loading all audio data. The samples are small, so a
+        // stalled download is given up on and retried sooner than the
+        // default.
+        let load_settings = LoadSettings::new()
+            .normalize(TARGET_LOUDNESS)
+            .timeout(std::time::Duration::from_secs(10))
+            .retries(5, std::time::Duration::from_millis(500));
+        let load_settings = match crate::utils::output_sample_rate() {
+            Some(sample_rate) => load_settings.resample(sample_rate, ResampleQuality::Sinc),
+            None => load_settings,
+        };
+        let batch = AssetBatch::load(
+            Self::MANIFEST_URL,
+            Self::ASSETS,
+            load_settings,
+            link.callback(|_| Message::Loading),
+            link.callback(Message::Loaded),
+        );
 
         let interval_service = IntervalService::spawn(
             std::time::Duration::from_secs_f32(1.0 / 30.0),
@@ -186,16 +256,12 @@ impl Component for DrumFillDemo {
 
         Self {
             link,
-            loop_sound: None,
-            fill_2b: None,
-            fill_3b: None,
-            fill_4b: None,
-            manager,
-            metronome,
-            group,
+            batch,
+            assets: None,
+            mixer: Mixer::new(),
+            loaded: None,
             beat_tracker: None,
             loop_sequence: None,
-            loaded: false,
             playback_state: PlaybackState::Stopped,
             _interval_service: interval_service,
         }
@@ -203,49 +269,16 @@ impl Component for DrumFillDemo {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Message::LoadedLoop(rate, frames) => {
-                self.loop_sound = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().groups(GroupSet::new().add(&self.group)),
-                    ))
-                    .ok();
-                self.check_loaded()
-            }
-            Message::LoadedFill2b(rate, frames) => {
-                self.fill_2b = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().groups(GroupSet::new().add(&self.group)),
-                    ))
-                    .ok();
-                self.check_loaded()
-            }
-            Message::LoadedFill3b(rate, frames) => {
-                self.fill_3b = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().groups(GroupSet::new().add(&self.group)),
-                    ))
-                    .ok();
-                self.check_loaded()
+            Message::Loading => self.assets.is_none(),
+            Message::Loaded(loaded) => {
+                self.loaded = Some(loaded);
+                self.build_assets();
+                true
             }
-            Message::LoadedFill4b(rate, frames) => {
-                self.fill_4b = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().groups(GroupSet::new().add(&self.group)),
-                    ))
-                    .ok();
-                self.check_loaded()
+            Message::RetryAudio => {
+                self.mixer = Mixer::new();
+                self.build_assets();
+                true
             }
             Message::PlayClick => {
                 match self.playback_state {
@@ -253,18 +286,19 @@ impl Component for DrumFillDemo {
                         self.playback_state = PlaybackState::PlayingLoop(Beat::One);
                         self.beat_tracker = Some(self.start_beat_tracker());
                         self.loop_sequence = Some(self.start_loop_sequence());
-                        self.metronome.start().unwrap();
-                    }
-                    _ => {
-                        self.group.stop(Default::default()).unwrap();
-                        self.metronome.stop().unwrap();
-                        self.playback_state = PlaybackState::Stopped;
-                        self.beat_tracker = None;
-                        self.loop_sequence = None;
+                        self.mixer.as_mut().unwrap().metronome.start().unwrap();
                     }
+                    _ => self.stop(Default::default()),
                 }
                 true
             }
+            Message::StopEverything => match self.playback_state {
+                PlaybackState::Stopped => false,
+                _ => {
+                    self.stop(fade_out());
+                    true
+                }
+            },
             Message::PlayFillClick => {
                 match self.playback_state {
                     PlaybackState::PlayingLoop(beat) => {
@@ -322,8 +356,31 @@ impl Component for DrumFillDemo {
         false
     }
 
+    fn destroy(&mut self) {
+        let mixer = match &mut self.mixer {
+            Ok(mixer) => mixer,
+            Err(_) => return,
+        };
+        mixer.group.stop(fade_out()).ok();
+        mixer.metronome.stop().ok();
+        let assets = self.assets.take();
+        let group_id = mixer.group.id();
+        let metronome_id = mixer.metronome.id();
+        teardown("drum fill demo", &mixer.audio, move |manager| {
+            let released = assets.map_or(0, |assets| assets.unload(manager));
+            manager.remove_group(group_id).ok();
+            manager.remove_metronome(metronome_id).ok();
+            released
+        });
+    }
+
     fn view(&self) -> Html {
-        if self.loaded {
+        if let Err(error) = &self.mixer {
+            error.view(
+                "drum fill demo",
+                self.link.callback(|_| Self::Message::RetryAudio),
+            )
+        } else if self.assets.is_some() {
             html! {
                 <>
                     <RouterButton<AppRoute> classes="small-button" route=AppRoute::Index>
@@ -348,39 +405,60 @@ impl Component for DrumFillDemo {
                         <div class="explanation centered">
                             { EXPLANATION_TEXT }
                         </div>
+                        { self.assets.as_ref().map_or(html! {}, |assets| assets.view_analysis()) }
                     </div>
                 </>
             }
         } else {
-            crate::utils::loading("drum fill demo")
+            self.batch.view("drum fill demo")
         }
     }
 }
 
 impl DrumFillDemo {
-    fn check_loaded(&mut self) -> ShouldRender {
-        if self.loaded {
-            return false;
-        }
-
-        if self.loop_sound.is_none()
-            || self.fill_2b.is_none()
-            || self.fill_3b.is_none()
-            || self.fill_4b.is_none()
-        {
-            return false;
+    /// Sets the tempo from the manifest and adds the drum samples to the
+    /// audio manager, once both they and the mixer are available.
+    fn build_assets(&mut self) {
+        let mixer = match &mut self.mixer {
+            Ok(mixer) => mixer,
+            Err(_) => return,
+        };
+        if let Some(loaded) = self.loaded.take() {
+            mixer
+                .metronome
+                .set_tempo(Tempo(loaded.manifest().tempo))
+                .ok();
+            let bindings = Bindings::new().group("drums", mixer.group.id());
+            match loaded.build(&mut mixer.audio.manager(), &bindings) {
+                Ok(assets) => {
+                    self.assets = Some(assets);
+                    mixer
+                        .audio
+                        .on_stop(self.link.callback(|_| Message::StopEverything));
+                }
+                Err(error) => self.batch.fail(error),
+            }
         }
+    }
 
-        self.loaded = true;
-        true
+    fn stop(&mut self, settings: StopInstanceSettings) {
+        let mixer = self.mixer.as_mut().unwrap();
+        mixer.group.stop(settings).unwrap();
+        mixer.metronome.stop().unwrap();
+        self.playback_state = PlaybackState::Stopped;
+        self.beat_tracker = None;
+        self.loop_sequence = None;
     }
 
     fn start_beat_tracker(&mut self) -> SequenceInstanceHandle<Beat> {
-        self.manager
+        let mixer = self.mixer.as_ref().unwrap();
+        mixer
+            .audio
+            .manager()
             .start_sequence(
                 {
                     let mut sequence = Sequence::new(
-                        SequenceSettings::new().groups(GroupSet::new().add(&self.group)),
+                        SequenceSettings::new().groups(GroupSet::new().add(&mixer.group)),
                     );
                     sequence.wait_for_interval(1.0);
                     sequence.start_loop();
@@ -394,25 +472,29 @@ impl DrumFillDemo {
                     sequence.wait(Duration::Beats(1.0));
                     sequence
                 },
-                SequenceInstanceSettings::new().metronome(&self.metronome),
+                SequenceInstanceSettings::new().metronome(&mixer.metronome),
             )
             .unwrap()
     }
 
     fn start_loop_sequence(&mut self) -> SequenceInstanceHandle<DrumFillEvent> {
-        self.manager
+        let assets = self.assets.as_ref().unwrap();
+        let mixer = self.mixer.as_ref().unwrap();
+        mixer
+            .audio
+            .manager()
             .start_sequence(
                 {
                     let mut sequence = Sequence::new(
-                        SequenceSettings::new().groups(GroupSet::new().add(&self.group)),
+                        SequenceSettings::new().groups(GroupSet::new().add(&mixer.group)),
                     );
                     sequence.wait_for_interval(1.0);
                     sequence.start_loop();
-                    sequence.play(self.loop_sound.as_ref().unwrap(), Default::default());
+                    sequence.play(assets.sound(Asset::Loop), Default::default());
                     sequence.wait(Duration::Beats(4.0));
                     sequence
                 },
-                SequenceInstanceSettings::new().metronome(&self.metronome),
+                SequenceInstanceSettings::new().metronome(&mixer.metronome),
             )
             .unwrap()
     }
@@ -422,11 +504,15 @@ impl DrumFillDemo {
         fill: DrumFill,
     ) -> SequenceInstanceHandle<DrumFillEvent> {
         let previous_loop_sequence = self.loop_sequence.take().unwrap();
-        self.manager
+        let assets = self.assets.as_ref().unwrap();
+        let mixer = self.mixer.as_ref().unwrap();
+        mixer
+            .audio
+            .manager()
             .start_sequence(
                 {
                     let mut sequence = Sequence::new(
-                        SequenceSettings::new().groups(GroupSet::new().add(&self.group)),
+                        SequenceSettings::new().groups(GroupSet::new().add(&mixer.group)),
                     );
                     sequence.wait_for_interval(fill.start_interval());
                     sequence.emit(DrumFillEvent::Start);
@@ -434,24 +520,22 @@ impl DrumFillDemo {
                         .stop_sequence_and_instances(&previous_loop_sequence, Default::default());
                     sequence.play(
                         {
-                            match fill {
-                                DrumFill::TwoBeat => &self.fill_2b,
-                                DrumFill::ThreeBeat => &self.fill_3b,
-                                DrumFill::FourBeat => &self.fill_4b,
-                            }
-                            .as_ref()
-                            .unwrap()
+                            assets.sound(match fill {
+                                DrumFill::TwoBeat => Asset::TwoBeatFill,
+                                DrumFill::ThreeBeat => Asset::ThreeBeatFill,
+                                DrumFill::FourBeat => Asset::FourBeatFill,
+                            })
                         },
                         Default::default(),
                     );
                     sequence.wait_for_interval(4.0);
                     sequence.emit(DrumFillEvent::Finish);
                     sequence.start_loop();
-                    sequence.play(self.loop_sound.as_ref().unwrap(), Default::default());
+                    sequence.play(assets.sound(Asset::Loop), Default::default());
                     sequence.wait(Duration::Beats(4.0));
                     sequence
                 },
-                SequenceInstanceSettings::new().metronome(&self.metronome),
+                SequenceInstanceSettings::new().metronome(&mixer.metronome),
             )
             .unwrap()
     }
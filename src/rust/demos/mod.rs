@@ -3,3 +3,140 @@ mod underwater_demo;
 
 pub use drum_fill_demo::DrumFillDemo;
 pub use underwater_demo::UnderwaterDemo;
+
+use crate::{
+    query::Query,
+    utils::{manifest::AssetKind, AssetName},
+    AppRoute,
+};
+use serde::Serialize;
+use yew::{html, Component, Html, Properties};
+use yew_router::{agent::RouteRequest, prelude::*};
+
+#[derive(Debug, Clone, Default, PartialEq, Properties)]
+pub struct DemoProps {
+    /// The query string of the demo's url, which demos parse their
+    /// settings from.
+    #[prop_or_default]
+    pub query: Query,
+}
+
+/// A demo, as listed on the selection page. The route table and the
+/// selection page are generated from [`registry`], so adding a demo only
+/// takes implementing this trait and adding it there.
+pub trait Demo: Component<Properties = DemoProps> {
+    const TITLE: &'static str;
+    /// The path the demo is served under, without slashes. The webpack
+    /// config needs a page with the same path.
+    const SLUG: &'static str;
+    /// A one sentence summary shown on the selection page.
+    const DESCRIPTION: &'static str;
+    /// The kira features the demo shows how to use.
+    const FEATURES: &'static [Feature];
+    /// Free-form keywords the selection page can filter demos by.
+    const TAGS: &'static [&'static str];
+    const MANIFEST_URL: &'static str;
+
+    type Asset: AssetName + Serialize;
+    /// The assets the demo loads from its manifest.
+    const ASSETS: &'static [(Self::Asset, AssetKind)];
+}
+
+/// The kira features a demo can exercise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Parameters,
+    Mappings,
+    Metronome,
+    Sequences,
+    Groups,
+    Effects,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Parameters => "parameters",
+            Feature::Mappings => "mappings",
+            Feature::Metronome => "metronome",
+            Feature::Sequences => "sequences",
+            Feature::Groups => "groups",
+            Feature::Effects => "effects",
+        }
+    }
+}
+
+/// The description of a demo, with its asset type erased.
+pub struct DemoInfo {
+    pub title: &'static str,
+    pub slug: &'static str,
+    pub description: &'static str,
+    pub features: &'static [Feature],
+    pub tags: &'static [&'static str],
+    pub manifest_url: &'static str,
+    /// The assets of the demo, under the names its manifest uses.
+    pub assets: Vec<(String, AssetKind)>,
+    view: fn(Query) -> Html,
+}
+
+impl DemoInfo {
+    fn of<D: Demo>() -> Self {
+        Self {
+            title: D::TITLE,
+            slug: D::SLUG,
+            description: D::DESCRIPTION,
+            features: D::FEATURES,
+            tags: D::TAGS,
+            manifest_url: D::MANIFEST_URL,
+            assets: D::ASSETS
+                .iter()
+                .map(|(asset, kind)| {
+                    let name = serde_json::to_value(asset)
+                        .ok()
+                        .and_then(|name| name.as_str().map(String::from))
+                        .unwrap_or_else(|| format!("{:?}", asset));
+                    (name, *kind)
+                })
+                .collect(),
+            view: |query| html! { <D query=query /> },
+        }
+    }
+
+    /// Whether `query`, in lowercase, appears in the title, description,
+    /// features or tags of the demo.
+    pub fn matches(&self, query: &str) -> bool {
+        let fields = [self.title, self.description];
+        fields
+            .iter()
+            .chain(self.tags)
+            .copied()
+            .chain(self.features.iter().map(|feature| feature.name()))
+            .any(|field| field.to_lowercase().contains(query))
+    }
+
+    /// Renders the demo's component, with the settings in `query`.
+    pub fn view(&self, query: Query) -> Html {
+        (self.view)(query)
+    }
+}
+
+/// Every demo, in the order the selection page lists them in.
+pub fn registry() -> Vec<DemoInfo> {
+    vec![
+        DemoInfo::of::<UnderwaterDemo>(),
+        DemoInfo::of::<DrumFillDemo>(),
+    ]
+}
+
+/// The demo served under `slug`, if any.
+pub fn find(slug: &str) -> Option<DemoInfo> {
+    registry().into_iter().find(|demo| demo.slug == slug)
+}
+
+/// Replaces the url of the current page with the one of demo `slug`
+/// with `query`, without adding a history entry, so that the url always
+/// links to the current settings of the demo.
+pub fn replace_query(slug: &str, query: &Query) {
+    let route = AppRoute::demo(slug, query);
+    RouteAgentDispatcher::<()>::new().send(RouteRequest::ReplaceRoute(route.into()));
+}
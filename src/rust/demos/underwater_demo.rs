@@ -1,17 +1,25 @@
-use crate::AppRoute;
+use super::{replace_query, Demo, DemoProps, Feature};
+use crate::{
+    query::Query,
+    utils::{
+        fade_out,
+        manifest::{AssetKind, Bindings},
+        teardown, AssetBatch, AssetHandles, AudioError, AudioSession, LoadSettings, LoadedAssets,
+        ResampleQuality,
+    },
+    AppRoute,
+};
 use kira::{
-    arrangement::{handle::ArrangementHandle, Arrangement, LoopArrangementSettings},
     instance::{InstanceSettings, StopInstanceSettings},
-    manager::AudioManager,
     mixer::{
         effect::filter::{Filter, FilterSettings},
         SubTrackHandle,
     },
     parameter::{handle::ParameterHandle, tween::Tween, Mapping, ParameterSettings},
     sequence::{handle::SequenceInstanceHandle, Sequence},
-    sound::{Sound, SoundSettings},
-    Frame, Tempo, Value,
+    Value,
 };
+use serde::{Deserialize, Serialize};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
@@ -22,158 +30,211 @@ the volume of the drums, and the volume of the pad.
 Each of these values uses a different mapping to properly \
 respond to the change in the \"underwater\" parameter.";
 
+/// The loudness the loudest stem is brought to, in LUFS. Every stem gets
+/// the same gain, so that they keep the balance they were mixed with,
+/// and the target leaves headroom for their sum.
+const TARGET_LOUDNESS: f64 = -20.0;
+
+const MANIFEST_URL: &str = "/underwater-demo/manifest.json";
+
+/// How long submerging and resurfacing take by default, in seconds.
+const DEFAULT_TWEEN: f64 = 4.0;
+/// The longest submerging and resurfacing time the slider allows.
+const MAX_TWEEN: f64 = 8.0;
+
+/// The state of the demo its url links to, as in
+/// `/underwater-demo?submerged=true&tween=2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    underwater: bool,
+    /// How long submerging and resurfacing take, in seconds.
+    tween: f64,
+}
+
+impl Settings {
+    fn from_query(query: &Query) -> Self {
+        Self {
+            underwater: query.get("submerged").unwrap_or(false),
+            tween: query
+                .get("tween")
+                .filter(|tween: &f64| (0.0..=MAX_TWEEN).contains(tween))
+                .unwrap_or(DEFAULT_TWEEN),
+        }
+    }
+
+    /// The value of the underwater parameter.
+    fn parameter_value(self) -> f64 {
+        if self.underwater {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn to_query(self) -> Query {
+        let mut query = Query::default();
+        query.set("submerged", self.underwater);
+        query.set("tween", self.tween);
+        query
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Asset {
+    Bass,
+    Pad,
+    Lead,
+    Drums,
+}
+
 pub struct UnderwaterDemo {
     link: ComponentLink<Self>,
 
-    bass: Option<ArrangementHandle>,
-    pad: Option<ArrangementHandle>,
-    lead: Option<ArrangementHandle>,
-    drums: Option<ArrangementHandle>,
+    batch: AssetBatch<Asset>,
+    assets: Option<AssetHandles<Asset>>,
 
-    manager: AudioManager,
-    lead_track_handle: SubTrackHandle,
-    underwater_parameter_handle: ParameterHandle,
+    mixer: Result<Mixer, AudioError>,
+    /// The stems, if they finished loading before the mixer could be
+    /// set up.
+    loaded: Option<LoadedAssets<Asset>>,
     sequence_handle: Option<SequenceInstanceHandle<()>>,
 
-    underwater: bool,
-    loaded: bool,
+    settings: Settings,
 }
 
 pub enum Message {
-    LoadedBass(u32, Vec<Frame>),
-    LoadedPad(u32, Vec<Frame>),
-    LoadedLead(u32, Vec<Frame>),
-    LoadedDrums(u32, Vec<Frame>),
+    Loading,
+    Loaded(LoadedAssets<Asset>),
 
     PlayButtonClick,
     SubmergeButtonClick,
+    TweenInput(String),
+    RetryAudio,
+    /// Stops the sequence, for the transport bar.
+    StopEverything,
+}
+
+/// What the demo adds to the shared audio manager, besides its assets.
+struct Mixer {
+    audio: AudioSession,
+    lead_track_handle: SubTrackHandle,
+    underwater_parameter_handle: ParameterHandle,
+}
+
+impl Mixer {
+    fn new(settings: Settings) -> Result<Self, AudioError> {
+        let audio = AudioSession::start()?;
+        let mut manager = audio.manager();
+        let mut lead_track_handle = manager
+            .add_sub_track(Default::default())
+            .map_err(AudioError::rejected)?;
+        let underwater_parameter_handle = match manager
+            .add_parameter(ParameterSettings::new().value(settings.parameter_value()))
+        {
+            Ok(handle) => handle,
+            Err(error) => {
+                manager.remove_sub_track(lead_track_handle.id()).ok();
+                return Err(AudioError::rejected(error));
+            }
+        };
+        if let Err(error) = lead_track_handle.add_effect(
+            Filter::new(FilterSettings::new().cutoff(Value::Parameter(
+                underwater_parameter_handle.id(),
+                Mapping {
+                    input_range: (0.0, 1.0),
+                    output_range: (8000.0, 2000.0),
+                    ..Default::default()
+                },
+            ))),
+            Default::default(),
+        ) {
+            manager.remove_sub_track(lead_track_handle.id()).ok();
+            manager
+                .remove_parameter(underwater_parameter_handle.id())
+                .ok();
+            return Err(AudioError::rejected(error));
+        }
+        drop(manager);
+
+        Ok(Self {
+            audio,
+            lead_track_handle,
+            underwater_parameter_handle,
+        })
+    }
+}
+
+impl Demo for UnderwaterDemo {
+    const TITLE: &'static str = "Underwater demo";
+    const SLUG: &'static str = "underwater-demo";
+    const DESCRIPTION: &'static str =
+        "A single parameter drives a filter cutoff and the volume of two stems.";
+
+    const FEATURES: &'static [Feature] = &[
+        Feature::Parameters,
+        Feature::Mappings,
+        Feature::Sequences,
+        Feature::Effects,
+    ];
+    const TAGS: &'static [&'static str] = &["mixing", "filter", "stems", "loops"];
+    const MANIFEST_URL: &'static str = MANIFEST_URL;
+
+    type Asset = Asset;
+    const ASSETS: &'static [(Asset, AssetKind)] = &[
+        (Asset::Bass, AssetKind::Loop),
+        (Asset::Pad, AssetKind::Loop),
+        (Asset::Lead, AssetKind::Loop),
+        (Asset::Drums, AssetKind::Loop),
+    ];
 }
 
 impl Component for UnderwaterDemo {
     type Message = Message;
-    type Properties = ();
-    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        // Start loading all audio data
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/underwater-demo/bass.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedBass(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/underwater-demo/pad.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedPad(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/underwater-demo/lead.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedLead(rate, frames))
-        });
-        let link_clone = link.clone();
-        crate::utils::load_audio_data("/underwater-demo/drums.ogg", move |rate, frames| {
-            link_clone.send_message(Message::LoadedDrums(rate, frames))
-        });
+    type Properties = DemoProps;
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let settings = Settings::from_query(&props.query);
 
-        let mut manager = AudioManager::new(Default::default()).unwrap();
-        let mut lead_track_handle = manager.add_sub_track(Default::default()).unwrap();
-        let underwater_parameter_handle = manager
-            .add_parameter(ParameterSettings::new().value(0.0))
-            .unwrap();
-        lead_track_handle
-            .add_effect(
-                Filter::new(FilterSettings::new().cutoff(Value::Parameter(
-                    underwater_parameter_handle.id(),
-                    Mapping {
-                        input_range: (0.0, 1.0),
-                        output_range: (8000.0, 2000.0),
-                        ..Default::default()
-                    },
-                ))),
-                Default::default(),
-            )
-            .unwrap();
+        // Start loading all audio data. The stems are large, so slow
+        // connections get more time for each attempt than the default.
+        let load_settings = LoadSettings::new()
+            .normalize(TARGET_LOUDNESS)
+            .timeout(std::time::Duration::from_secs(90));
+        let load_settings = match crate::utils::output_sample_rate() {
+            Some(sample_rate) => load_settings.resample(sample_rate, ResampleQuality::Sinc),
+            None => load_settings,
+        };
+        let batch = AssetBatch::load(
+            Self::MANIFEST_URL,
+            Self::ASSETS,
+            load_settings,
+            link.callback(|_| Message::Loading),
+            link.callback(Message::Loaded),
+        );
 
         Self {
             link,
-            bass: None,
-            pad: None,
-            lead: None,
-            drums: None,
-            manager,
-            lead_track_handle,
-            underwater_parameter_handle,
+            batch,
+            assets: None,
+            mixer: Mixer::new(settings),
+            loaded: None,
             sequence_handle: None,
-            underwater: false,
-            loaded: false,
+            settings,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Self::Message::LoadedBass(rate, frames) => {
-                self.bass = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().semantic_duration(Tempo(85.0).beats_to_seconds(16.0)),
-                    ))
-                    .ok()
-                    .and_then(|sound| {
-                        self.manager
-                            .add_arrangement(Arrangement::new_loop(&sound, Default::default()))
-                            .ok()
-                    });
-                self.check_loaded()
-            }
-            Self::Message::LoadedPad(rate, frames) => {
-                self.pad = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().semantic_duration(Tempo(85.0).beats_to_seconds(16.0)),
-                    ))
-                    .ok()
-                    .and_then(|sound| {
-                        self.manager
-                            .add_arrangement(Arrangement::new_loop(&sound, Default::default()))
-                            .ok()
-                    });
-                self.check_loaded()
-            }
-            Self::Message::LoadedLead(rate, frames) => {
-                self.lead = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().semantic_duration(Tempo(85.0).beats_to_seconds(16.0)),
-                    ))
-                    .ok()
-                    .and_then(|sound| {
-                        self.manager
-                            .add_arrangement(Arrangement::new_loop(
-                                &sound,
-                                LoopArrangementSettings::new()
-                                    .default_track(self.lead_track_handle.id()),
-                            ))
-                            .ok()
-                    });
-                self.check_loaded()
+            Self::Message::Loading => self.assets.is_none(),
+            Self::Message::Loaded(loaded) => {
+                self.loaded = Some(loaded);
+                self.build_assets();
+                true
             }
-            Self::Message::LoadedDrums(rate, frames) => {
-                self.drums = self
-                    .manager
-                    .add_sound(Sound::from_frames(
-                        rate,
-                        frames,
-                        SoundSettings::new().semantic_duration(Tempo(85.0).beats_to_seconds(16.0)),
-                    ))
-                    .ok()
-                    .and_then(|sound| {
-                        self.manager
-                            .add_arrangement(Arrangement::new_loop(&sound, Default::default()))
-                            .ok()
-                    });
-                self.check_loaded()
+            Self::Message::RetryAudio => {
+                self.mixer = Mixer::new(self.settings);
+                self.build_assets();
+                true
             }
             Self::Message::PlayButtonClick => {
                 if let Some(ref mut sequence_handle) = self.sequence_handle {
@@ -184,15 +245,18 @@ impl Component for UnderwaterDemo {
                         .ok();
                     self.sequence_handle = None;
                 } else {
-                    let sequence_handle = self
-                        .manager
+                    let assets = self.assets.as_ref().unwrap();
+                    let mixer = self.mixer.as_ref().unwrap();
+                    let sequence_handle = mixer
+                        .audio
+                        .manager()
                         .start_sequence(
                             {
                                 let mut sequence = Sequence::<()>::new(Default::default());
                                 sequence.play(
-                                    self.drums.as_ref().unwrap().id(),
+                                    assets.arrangement(Asset::Drums).id(),
                                     InstanceSettings::new().volume(Value::Parameter(
-                                        self.underwater_parameter_handle.id(),
+                                        mixer.underwater_parameter_handle.id(),
                                         Mapping {
                                             input_range: (0.0, 1.0),
                                             output_range: (1.0, 0.0),
@@ -200,13 +264,15 @@ impl Component for UnderwaterDemo {
                                         },
                                     )),
                                 );
-                                sequence.play(self.bass.as_ref().unwrap().id(), Default::default());
+                                sequence
+                                    .play(assets.arrangement(Asset::Bass).id(), Default::default());
                                 sequence.play(
-                                    self.pad.as_ref().unwrap().id(),
+                                    assets.arrangement(Asset::Pad).id(),
                                     InstanceSettings::new()
-                                        .volume(self.underwater_parameter_handle.id()),
+                                        .volume(mixer.underwater_parameter_handle.id()),
                                 );
-                                sequence.play(self.lead.as_ref().unwrap().id(), Default::default());
+                                sequence
+                                    .play(assets.arrangement(Asset::Lead).id(), Default::default());
                                 sequence
                             },
                             Default::default(),
@@ -217,28 +283,74 @@ impl Component for UnderwaterDemo {
 
                 true
             }
-            Self::Message::SubmergeButtonClick => {
-                if self.underwater {
-                    self.underwater_parameter_handle
-                        .set(0.0, Some(4.0.into()))
-                        .ok();
-                } else {
-                    self.underwater_parameter_handle
-                        .set(1.0, Some(4.0.into()))
-                        .ok();
+            Self::Message::StopEverything => match self.sequence_handle.take() {
+                Some(mut sequence_handle) => {
+                    sequence_handle.stop_sequence_and_instances(fade_out()).ok();
+                    true
                 }
-                self.underwater = !self.underwater;
+                None => false,
+            },
+            Self::Message::SubmergeButtonClick => {
+                self.settings.underwater = !self.settings.underwater;
+                self.set_underwater_parameter();
+                replace_query(Self::SLUG, &self.settings.to_query());
                 true
             }
+            Self::Message::TweenInput(value) => match value.parse() {
+                Ok(tween) => {
+                    self.settings.tween = tween;
+                    replace_query(Self::SLUG, &self.settings.to_query());
+                    true
+                }
+                Err(_) => false,
+            },
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        // The url changes either because the settings changed, in which
+        // case it already matches them, or by navigating the history.
+        let settings = Settings::from_query(&props.query);
+        if settings == self.settings {
+            return false;
+        }
+        let underwater_changed = settings.underwater != self.settings.underwater;
+        self.settings = settings;
+        if underwater_changed {
+            self.set_underwater_parameter();
+        }
+        true
+    }
+
+    fn destroy(&mut self) {
+        if let Some(ref mut sequence_handle) = self.sequence_handle {
+            sequence_handle.stop_sequence_and_instances(fade_out()).ok();
+        }
+        let mixer = match &self.mixer {
+            Ok(mixer) => mixer,
+            Err(_) => return,
+        };
+        let assets = self.assets.take().map(|mut assets| {
+            assets.stop(fade_out());
+            assets
+        });
+        let lead_track_id = mixer.lead_track_handle.id();
+        let underwater_parameter_id = mixer.underwater_parameter_handle.id();
+        teardown("underwater demo", &mixer.audio, move |manager| {
+            let released = assets.map_or(0, |assets| assets.unload(manager));
+            manager.remove_sub_track(lead_track_id).ok();
+            manager.remove_parameter(underwater_parameter_id).ok();
+            released
+        });
     }
 
     fn view(&self) -> Html {
-        if self.loaded {
+        if let Err(error) = &self.mixer {
+            error.view(
+                "underwater demo",
+                self.link.callback(|_| Self::Message::RetryAudio),
+            )
+        } else if self.assets.is_some() {
             html! {
                 <>
                     <RouterButton<AppRoute> classes="small-button" route=AppRoute::Index>
@@ -250,33 +362,66 @@ impl Component for UnderwaterDemo {
                                 {if self.sequence_handle.is_none() { "Play" } else { "Stop" }}
                             </button>
                             <button onclick=self.link.callback(|_| Self::Message::SubmergeButtonClick)>
-                                {if self.underwater { "Resurface" } else { "Submerge" }}
+                                {if self.settings.underwater { "Resurface" } else { "Submerge" }}
                             </button>
                         </div>
+                        <div class="slider-panel">
+                            <label>{ format!("Transition: {:.1} s", self.settings.tween) }</label>
+                            <input
+                                type="range"
+                                min="0"
+                                max=MAX_TWEEN.to_string()
+                                step="0.5"
+                                value=self.settings.tween.to_string()
+                                oninput=self.link.callback(|event: InputData| Self::Message::TweenInput(event.value))
+                            />
+                        </div>
                         <div class="explanation centered">
                             { EXPLANATION_TEXT }
                         </div>
+                        { self.assets.as_ref().map_or(html! {}, |assets| assets.view_analysis()) }
                     </div>
                 </>
             }
         } else {
-            crate::utils::loading("underwater demo")
+            self.batch.view("underwater demo")
         }
     }
 }
 
 impl UnderwaterDemo {
-    fn check_loaded(&mut self) -> ShouldRender {
-        if self.loaded {
-            return false;
+    /// Adds the stems to the audio manager, if they are loaded and the
+    /// mixer is set up. Runs whenever either of the two happens.
+    fn build_assets(&mut self) {
+        let mixer = match &self.mixer {
+            Ok(mixer) => mixer,
+            Err(_) => return,
+        };
+        if let Some(loaded) = self.loaded.take() {
+            let bindings = Bindings::new().track("lead", mixer.lead_track_handle.id());
+            match loaded.build(&mut mixer.audio.manager(), &bindings) {
+                Ok(assets) => {
+                    self.assets = Some(assets);
+                    mixer
+                        .audio
+                        .on_stop(self.link.callback(|_| Message::StopEverything));
+                }
+                Err(error) => self.batch.fail(error),
+            }
         }
+    }
 
-        if self.bass.is_none() || self.lead.is_none() || self.drums.is_none() || self.pad.is_none()
-        {
-            return false;
+    /// Moves the underwater parameter to match the settings, over the
+    /// transition time they give.
+    fn set_underwater_parameter(&mut self) {
+        if let Ok(mixer) = &mut self.mixer {
+            mixer
+                .underwater_parameter_handle
+                .set(
+                    self.settings.parameter_value(),
+                    Some(self.settings.tween.into()),
+                )
+                .ok();
         }
-
-        self.loaded = true;
-        true
     }
 }
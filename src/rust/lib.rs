@@ -5,17 +5,44 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 
 mod demos;
+mod not_found;
+mod query;
 mod select_demo;
+mod transport;
 mod utils;
 
 #[derive(Switch, Debug, Clone)]
 pub enum AppRoute {
-    #[to = "/underwater-demo"]
-    UnderwaterDemo,
-    #[to = "/drum-fill-demo"]
-    DrumFillDemo,
-    #[to = "/"]
+    /// A demo from [`demos::registry`], by slug, with the settings it
+    /// reads from the query string.
+    #[to = "/{}?{*}"]
+    DemoWithQuery(String, String),
+    /// The same, with the trailing slash of the demo's own `index.html`
+    /// page, as static hosts serve it.
+    #[to = "/{}/?{*}"]
+    DemoPageWithQuery(String, String),
+    /// A demo from [`demos::registry`], by slug.
+    #[to = "/{}!"]
+    Demo(String),
+    /// The same, with the trailing slash of the demo's own `index.html`.
+    #[to = "/{}/!"]
+    DemoPage(String),
+    #[to = "/!"]
     Index,
+    /// Any other path, including anything below a demo's path.
+    #[to = "{*}"]
+    NotFound(String),
+}
+
+impl AppRoute {
+    /// The route of demo `slug`, with `query` if it is not empty.
+    pub fn demo(slug: &str, query: &query::Query) -> Self {
+        if query.is_empty() {
+            AppRoute::Demo(slug.into())
+        } else {
+            AppRoute::DemoWithQuery(slug.into(), query.to_string())
+        }
+    }
 }
 
 struct Main;
@@ -38,20 +65,48 @@ impl Component for Main {
 
     fn view(&self) -> Html {
         html! {
-            <Router<AppRoute, ()>
-                render = Router::render(|switch: AppRoute| {
-                    match switch {
-                        AppRoute::Index => html!{<select_demo::SelectDemo />},
-                        AppRoute::UnderwaterDemo => html!{<demos::UnderwaterDemo />},
-                        AppRoute::DrumFillDemo => html!{<demos::DrumFillDemo />},
-                    }
-                })
-            />
+            <>
+                <transport::Transport />
+                <Router<AppRoute, ()>
+                    render = Router::render(|switch: AppRoute| {
+                        match switch {
+                            AppRoute::DemoWithQuery(slug, query)
+                            | AppRoute::DemoPageWithQuery(slug, query) => {
+                                view_demo(&slug, query::Query::parse(&query))
+                            }
+                            AppRoute::Demo(slug) | AppRoute::DemoPage(slug) => {
+                                view_demo(&slug, Default::default())
+                            }
+                            AppRoute::Index => html!{<select_demo::SelectDemo />},
+                            AppRoute::NotFound(path) => html!{<not_found::NotFound path=path />},
+                        }
+                    })
+                />
+            </>
         }
     }
 }
 
+fn view_demo(slug: &str, query: query::Query) -> Html {
+    match demos::find(slug) {
+        Some(demo) => demo.view(query),
+        None => html! {<not_found::NotFound path=format!("/{}", slug) />},
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn run_app() {
+    // The module is also instantiated by the decoder worker, which has
+    // no page to mount to.
+    if web_sys::window().is_none() {
+        return;
+    }
     App::<Main>::new().mount_to_body();
 }
+
+/// Entry point of the decoder worker, called by `src/decoder_worker.js`.
+#[cfg(feature = "vorbis")]
+#[wasm_bindgen]
+pub fn run_decoder_worker() {
+    utils::run_worker();
+}
@@ -0,0 +1,156 @@
+use crate::{
+    demos::{self, DemoInfo},
+    AppRoute,
+};
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Properties)]
+pub struct Props {
+    /// The path that did not match any page.
+    pub path: String,
+}
+
+pub struct NotFound {
+    props: Props,
+}
+
+impl Component for NotFound {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <div class="container">
+                    <div class="title">{ "Page not found" }</div>
+                    <div class="explanation centered">
+                        { format!("There is no demo at \"{}\".", self.props.path) }
+                    </div>
+                </div>
+                { closest_demo(&self.props.path).map_or(html! {}, |demo| html! {
+                    <>
+                        <div class="demo-description centered">{ "Did you mean:" }</div>
+                        <RouterButton<AppRoute> classes="centered" route=AppRoute::Demo(demo.slug.into())>
+                            { demo.title }
+                        </RouterButton<AppRoute>>
+                        <br />
+                    </>
+                }) }
+                <RouterButton<AppRoute> classes="centered" route=AppRoute::Index>
+                    { "All demos" }
+                </RouterButton<AppRoute>>
+            </>
+        }
+    }
+}
+
+/// The demo whose slug is closest to the first segment of `path`, if it
+/// is close enough to be a likely typo.
+fn closest_demo(path: &str) -> Option<DemoInfo> {
+    let requested = path
+        .trim_start_matches('/')
+        .split(|c| c == '/' || c == '?' || c == '#')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if requested.is_empty() {
+        return None;
+    }
+    // Allow about one typo every three characters.
+    let max_distance = (requested.chars().count() / 3).max(1);
+    demos::registry()
+        .into_iter()
+        .map(|demo| (edit_distance(&requested, demo.slug), demo))
+        .filter(|(distance, demo)| {
+            *distance <= max_distance || demo.slug.starts_with(requested.as_str())
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, demo)| demo)
+}
+
+/// The Levenshtein distance between `a` and `b`: how many characters
+/// have to be inserted, removed or replaced to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + (a != *b) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closest_slug(path: &str) -> Option<&'static str> {
+        closest_demo(path).map(|demo| demo.slug)
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("demo", "demo"), 0);
+        assert_eq!(edit_distance("demo", ""), 4);
+        assert_eq!(edit_distance("", "demo"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("sitting", "kitten"), 3);
+        // Characters are compared, not bytes.
+        assert_eq!(edit_distance("démo", "demo"), 1);
+    }
+
+    #[test]
+    fn typos_suggest_the_closest_demo() {
+        assert_eq!(closest_slug("/underwater-demo"), Some("underwater-demo"));
+        assert_eq!(closest_slug("/underwater-dmeo"), Some("underwater-demo"));
+        assert_eq!(closest_slug("/Drum-Fill-Demos"), Some("drum-fill-demo"));
+    }
+
+    #[test]
+    fn prefixes_suggest_the_demo_they_start() {
+        assert_eq!(closest_slug("/drum"), Some("drum-fill-demo"));
+        assert_eq!(closest_slug("/under"), Some("underwater-demo"));
+    }
+
+    #[test]
+    fn only_the_first_segment_is_compared() {
+        assert_eq!(
+            closest_slug("/underwater-demo/garbage?volume=1#top"),
+            Some("underwater-demo")
+        );
+        assert_eq!(
+            closest_slug("/drum-fill-demo?fill=3"),
+            Some("drum-fill-demo")
+        );
+    }
+
+    #[test]
+    fn unrelated_paths_suggest_nothing() {
+        assert_eq!(closest_slug("/foo/bar"), None);
+        assert_eq!(closest_slug("/"), None);
+        assert_eq!(closest_slug(""), None);
+    }
+}
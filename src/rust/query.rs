@@ -0,0 +1,70 @@
+use std::{fmt, str::FromStr};
+
+/// The parameters of a url's query string, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    params: Vec<(String, String)>,
+}
+
+impl Query {
+    /// Parses `query`, with or without its leading `?`. Parameters that
+    /// cannot be decoded are skipped.
+    pub fn parse(query: &str) -> Self {
+        let params = query
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter_map(|param| {
+                let mut parts = param.splitn(2, '=');
+                let key = decode(parts.next()?)?;
+                let value = decode(parts.next().unwrap_or_default())?;
+                Some((key, value))
+            })
+            .collect();
+        Self { params }
+    }
+
+    /// The value of parameter `key`, if it is present and parses as `T`.
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.params
+            .iter()
+            .find(|(name, _)| name == key)
+            .and_then(|(_, value)| value.parse().ok())
+    }
+
+    /// Sets parameter `key`, replacing its previous value if any.
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+        match self.params.iter_mut().find(|(name, _)| name == key) {
+            Some((_, previous)) => *previous = value,
+            None => self.params.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Formats the query without its leading `?`.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, value)) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, "&")?;
+            }
+            write!(f, "{}={}", encode(key), encode(value))?;
+        }
+        Ok(())
+    }
+}
+
+fn decode(component: &str) -> Option<String> {
+    js_sys::decode_uri_component(&component.replace('+', " "))
+        .ok()
+        .map(String::from)
+}
+
+fn encode(component: &str) -> String {
+    js_sys::encode_uri_component(component).into()
+}
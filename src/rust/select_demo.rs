@@ -1,18 +1,73 @@
-use crate::AppRoute;
+use crate::{
+    demos::{self, DemoInfo},
+    utils::{manifest::load_download_size, LoadTask},
+    AppRoute,
+};
+use std::collections::HashMap;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-pub struct SelectDemo;
+pub struct SelectDemo {
+    link: ComponentLink<Self>,
+
+    demos: Vec<DemoInfo>,
+    /// The download size of every demo's assets, once measured.
+    sizes: HashMap<&'static str, Option<u64>>,
+    _size_tasks: Vec<LoadTask>,
+
+    /// The search text, in lowercase.
+    query: String,
+    /// Only demos with this tag are listed, if set.
+    tag: Option<&'static str>,
+}
+
+pub enum Message {
+    Search(String),
+    ToggleTag(&'static str),
+    Measured(&'static str, Option<u64>),
+}
 
 impl Component for SelectDemo {
-    type Message = ();
+    type Message = Message;
     type Properties = ();
-    fn create(_: Self::Properties, _: ComponentLink<Self>) -> Self {
-        Self
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let demos = demos::registry();
+        let size_tasks = demos
+            .iter()
+            .map(|demo| {
+                let slug = demo.slug;
+                let link = link.clone();
+                load_download_size(demo.manifest_url, move |size| {
+                    link.send_message(Message::Measured(slug, size))
+                })
+            })
+            .collect();
+
+        Self {
+            link,
+            demos,
+            sizes: HashMap::new(),
+            _size_tasks: size_tasks,
+            query: String::new(),
+            tag: None,
+        }
     }
 
-    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
-        false
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::Search(query) => self.query = query.trim().to_lowercase(),
+            Message::ToggleTag(tag) => {
+                self.tag = if self.tag == Some(tag) {
+                    None
+                } else {
+                    Some(tag)
+                }
+            }
+            Message::Measured(slug, size) => {
+                self.sizes.insert(slug, size);
+            }
+        }
+        true
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
@@ -20,19 +75,86 @@ impl Component for SelectDemo {
     }
 
     fn view(&self) -> Html {
+        let visible: Vec<&DemoInfo> = self
+            .demos
+            .iter()
+            .filter(|demo| demo.matches(&self.query))
+            .filter(|demo| self.tag.map_or(true, |tag| demo.tags.contains(&tag)))
+            .collect();
+
         html! {
             <>
                 <div class="container">
                     <div class="title">{"Select a demo"}</div>
+                    <input
+                        class="gallery-search"
+                        placeholder="Search by name, feature or tag"
+                        oninput=self.link.callback(|event: InputData| Message::Search(event.value))
+                    />
+                    <div class="tag-list">
+                        { for self.all_tags().into_iter().map(|tag| self.view_tag(tag)) }
+                    </div>
                 </div>
-                <RouterButton<AppRoute> classes="centered" route=AppRoute::UnderwaterDemo>
-                    { "Underwater demo" }
-                </RouterButton<AppRoute>>
-                <br />
-                <RouterButton<AppRoute> classes="centered" route=AppRoute::DrumFillDemo>
-                    { "Drum fill demo" }
-                </RouterButton<AppRoute>>
+                { for visible.iter().map(|demo| self.view_demo(demo)) }
+                { if visible.is_empty() {
+                    html! {
+                        <div class="demo-description centered">{ "No demo matches the search." }</div>
+                    }
+                } else {
+                    html! {}
+                } }
             </>
         }
     }
 }
+
+impl SelectDemo {
+    /// The tags of every demo, without duplicates, in alphabetical order.
+    fn all_tags(&self) -> Vec<&'static str> {
+        let mut tags: Vec<&'static str> = self
+            .demos
+            .iter()
+            .flat_map(|demo| demo.tags.iter().copied())
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    fn view_tag(&self, tag: &'static str) -> Html {
+        html! {
+            <button
+                class=if self.tag == Some(tag) { "tag selected-tag" } else { "tag" }
+                onclick=self.link.callback(move |_| Message::ToggleTag(tag))
+            >
+                { tag }
+            </button>
+        }
+    }
+
+    fn view_demo(&self, demo: &DemoInfo) -> Html {
+        let size = match self.sizes.get(demo.slug) {
+            Some(Some(bytes)) => format!("{:.1} MB", *bytes as f64 / 1_000_000.0),
+            Some(None) => "unknown size".into(),
+            None => "measuring...".into(),
+        };
+        let features: Vec<&str> = demo.features.iter().map(|feature| feature.name()).collect();
+        html! {
+            <div class="gallery-card centered">
+                <RouterButton<AppRoute> route=AppRoute::Demo(demo.slug.into())>
+                    { demo.title }
+                </RouterButton<AppRoute>>
+                <div class="demo-description">{ demo.description }</div>
+                <div class="demo-details">
+                    { format!("Uses {}", features.join(", ")) }
+                </div>
+                <div class="demo-details">
+                    { format!("{} assets, {}", demo.assets.len(), size) }
+                </div>
+                <div class="tag-list">
+                    { for demo.tags.iter().map(|&tag| self.view_tag(tag)) }
+                </div>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::utils;
+use yew::prelude::*;
+
+/// The header shown above every page, which controls the main track of
+/// the shared audio manager, whichever demo is mounted.
+pub struct Transport {
+    link: ComponentLink<Self>,
+    volume: f64,
+    muted: bool,
+}
+
+pub enum Message {
+    VolumeInput(String),
+    MuteClick,
+    StopClick,
+}
+
+impl Component for Transport {
+    type Message = Message;
+    type Properties = ();
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            volume: utils::master_volume(),
+            muted: utils::is_muted(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Message::VolumeInput(value) => match value.parse() {
+                Ok(volume) => {
+                    self.volume = volume;
+                    utils::set_master_volume(self.volume, self.muted);
+                    true
+                }
+                Err(_) => false,
+            },
+            Message::MuteClick => {
+                self.muted = !self.muted;
+                utils::set_master_volume(self.volume, self.muted);
+                true
+            }
+            Message::StopClick => {
+                utils::stop_everything();
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, _: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <header class="transport-bar">
+                <label>{ format!("Volume: {:.0}%", self.volume * 100.0) }</label>
+                <input
+                    type="range"
+                    min="0"
+                    max="1"
+                    step="0.05"
+                    value=self.volume.to_string()
+                    oninput=self.link.callback(|event: InputData| Message::VolumeInput(event.value))
+                />
+                <button onclick=self.link.callback(|_| Message::MuteClick)>
+                    { if self.muted { "Unmute" } else { "Mute" } }
+                </button>
+                <button onclick=self.link.callback(|_| Message::StopClick)>
+                    { "Stop everything" }
+                </button>
+            </header>
+        }
+    }
+}
@@ -1,66 +1,756 @@
+mod audio_session;
+mod batch;
+mod cache;
+mod channels;
+#[cfg(feature = "vorbis")]
+mod decoder_worker;
+mod format;
+mod loudness;
+pub mod manifest;
+mod persistent_cache;
+mod progress;
+mod resample;
+mod silence;
+#[cfg(feature = "vorbis")]
+mod stream;
+mod teardown;
+#[cfg(feature = "vorbis")]
+mod vorbis;
+mod web_audio;
+
+pub use audio_session::{
+    is_muted, master_volume, set_master_volume, stop_everything, AudioError, AudioSession,
+};
+pub use batch::{AssetBatch, AssetHandles, AssetName, LoadedAssets};
+#[cfg(feature = "vorbis")]
+pub use decoder_worker::run_worker;
+pub use format::{AudioFormat, AudioSource};
+pub use loudness::Loudness;
+pub use progress::LoadProgress;
+pub use resample::ResampleQuality;
+#[cfg(feature = "vorbis")]
+pub use stream::StreamingSound;
+pub use teardown::{fade_out, teardown};
+pub use web_audio::output_sample_rate;
+
 use crate::AppRoute;
-use js_sys::ArrayBuffer;
+use js_sys::{ArrayBuffer, Function, Promise, Reflect, Uint8Array};
 use kira::Frame;
+use progress::AssetProgress;
+use serde::{Deserialize, Serialize};
+use std::{fmt, rc::Rc, time::Duration};
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioBuffer, AudioContext, Request, RequestInit, RequestMode, Response};
-use yew::{html, Html};
+use web_sys::{
+    AbortController, AbortSignal, IdbFactory, Request, RequestInit, RequestMode, Response, Window,
+    WorkerGlobalScope,
+};
+use yew::{html, Callback, Html, MouseEvent};
 use yew_router::prelude::*;
 
-pub fn load_audio_data(url: &'static str, callback: impl FnOnce(u32, Vec<Frame>) + 'static) {
-    std::mem::drop(wasm_bindgen_futures::future_to_promise(
-        load_audio_data_async(url, callback),
-    ));
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LoadError {
+    Network(String),
+    HttpStatus(u16),
+    /// An attempt took longer than [`LoadSettings::timeout`].
+    Timeout(Duration),
+    Undecodable(String),
+    UnsupportedChannelLayout(u32),
+    /// None of the encodings the asset is available in can be decoded
+    /// in this browser.
+    UnsupportedFormat,
+    InvalidManifest(String),
+    /// The audio manager refused the sound or arrangement built from
+    /// the asset.
+    Rejected(String),
+    /// The load was cancelled by dropping its [`LoadTask`].
+    Aborted,
+}
+
+impl LoadError {
+    fn network(value: JsValue) -> Self {
+        LoadError::Network(describe_js_error(value))
+    }
+
+    /// Whether trying again later might succeed.
+    fn is_transient(&self) -> bool {
+        match self {
+            LoadError::Network(_) | LoadError::Timeout(_) => true,
+            LoadError::HttpStatus(status) => matches!(status, 408 | 429 | 500..=599),
+            _ => false,
+        }
+    }
+
+    /// Whether the asset was downloaded but could not be decoded, in
+    /// which case another encoding of it might work.
+    fn is_decoding_failure(&self) -> bool {
+        matches!(
+            self,
+            LoadError::Undecodable(_) | LoadError::UnsupportedChannelLayout(_)
+        )
+    }
+
+    fn undecodable(value: JsValue) -> Self {
+        LoadError::Undecodable(describe_js_error(value))
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Network(reason) => write!(f, "network error ({})", reason),
+            LoadError::HttpStatus(status) => write!(f, "server answered with HTTP {}", status),
+            LoadError::Timeout(timeout) => {
+                write!(f, "timed out after {:.1} seconds", timeout.as_secs_f64())
+            }
+            LoadError::Undecodable(reason) => write!(f, "could not decode audio ({})", reason),
+            LoadError::UnsupportedChannelLayout(channels) => {
+                write!(f, "unsupported channel layout ({} channels)", channels)
+            }
+            LoadError::UnsupportedFormat => {
+                write!(f, "this browser cannot decode any of the available formats")
+            }
+            LoadError::InvalidManifest(reason) => write!(f, "invalid manifest ({})", reason),
+            LoadError::Rejected(reason) => {
+                write!(f, "the audio manager rejected the asset ({})", reason)
+            }
+            LoadError::Aborted => write!(f, "the load was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn describe_js_error(value: JsValue) -> String {
+    match value.dyn_ref::<js_sys::Error>() {
+        Some(error) => error.message().into(),
+        None => value.as_string().unwrap_or_else(|| format!("{:?}", value)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LoadSettings {
+    /// The sample rate every asset should be converted to, and the
+    /// quality of that conversion.
+    pub resample: Option<(u32, ResampleQuality)>,
+    /// How long a single attempt at downloading an asset may take,
+    /// body included, before it is aborted.
+    pub timeout: Option<Duration>,
+    /// How many times a download that failed for a transient reason
+    /// (network error, timeout, server error) is tried again.
+    pub max_retries: u32,
+    /// How long to wait before the first retry. The delay doubles with
+    /// every following retry.
+    pub backoff: Duration,
+    /// If set, decoded assets are also stored in IndexedDB, so that
+    /// they survive page reloads. Records stored under another version
+    /// are ignored and replaced. Batches use the hash of their
+    /// manifest, so that editing a manifest invalidates its assets.
+    pub cache_version: Option<u64>,
+    /// The integrated loudness, in LUFS, every asset should be brought
+    /// to. The gain is reduced for assets that would clip otherwise.
+    /// Batches apply a single gain to all their assets instead, which
+    /// brings the loudest of them to this loudness, so that assets mixed
+    /// together keep their balance.
+    pub normalize: Option<f64>,
+    /// Whether to remove the short silence encoders add to the start
+    /// of files, which would otherwise delay every sound and make loops
+    /// longer than they should be. Batches take this from the manifest
+    /// entry of each asset, since trimming assets that play together
+    /// by different amounts would put them out of sync.
+    pub trim_silence: bool,
 }
 
+impl LoadSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    pub fn retries(self, max_retries: u32, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            ..self
+        }
+    }
+
+    pub fn persistent_cache(self, version: u64) -> Self {
+        Self {
+            cache_version: Some(version),
+            ..self
+        }
+    }
+
+    pub fn normalize(self, target: f64) -> Self {
+        Self {
+            normalize: Some(target),
+            ..self
+        }
+    }
+
+    pub fn resample(self, sample_rate: u32, quality: ResampleQuality) -> Self {
+        Self {
+            resample: Some((sample_rate, quality)),
+            ..self
+        }
+    }
+}
+
+impl Default for LoadSettings {
+    fn default() -> Self {
+        Self {
+            resample: None,
+            timeout: Some(Duration::from_secs(30)),
+            max_retries: 3,
+            backoff: Duration::from_secs(1),
+            cache_version: None,
+            normalize: None,
+            trim_silence: false,
+        }
+    }
+}
+
+/// A load started in the background. Dropping it aborts any request
+/// still in flight, and guarantees that the callbacks of the load are
+/// never called afterwards.
+#[must_use = "dropping a LoadTask cancels the load"]
+pub struct LoadTask {
+    controller: AbortController,
+}
+
+impl LoadTask {
+    fn new() -> Self {
+        Self {
+            controller: AbortController::new().expect("could not create an abort controller"),
+        }
+    }
+
+    fn signal(&self) -> AbortSignal {
+        self.controller.signal()
+    }
+}
+
+impl Drop for LoadTask {
+    fn drop(&mut self) {
+        self.controller.abort();
+    }
+}
+
+/// Decoded audio data, ready to be turned into a kira sound.
+#[derive(Debug, Clone)]
+pub struct LoadedAudio {
+    pub sample_rate: u32,
+    /// The audio as decoded and resampled, shared with the decoded
+    /// audio cache. Trimming and normalization are only applied by
+    /// [`LoadedAudio::to_frames`].
+    pub frames: Rc<[Frame]>,
+    pub loudness: Loudness,
+    /// How many frames of leading silence are trimmed.
+    pub trimmed: usize,
+}
+
+impl LoadedAudio {
+    /// Copies the audio for a kira sound to own, without its leading
+    /// silence and with the normalization gain applied.
+    pub fn to_frames(&self) -> Vec<Frame> {
+        let mut frames = self.frames[self.trimmed..].to_vec();
+        if self.loudness.gain != 0.0 {
+            loudness::apply_gain(&mut frames, self.loudness.gain);
+        }
+        frames
+    }
+}
+
+pub fn load_audio_data(
+    sources: Vec<AudioSource>,
+    settings: LoadSettings,
+    mut on_progress: impl FnMut(LoadProgress) + 'static,
+    callback: impl FnOnce(Result<LoadedAudio, LoadError>) + 'static,
+) -> LoadTask {
+    let task = LoadTask::new();
+    let signal = task.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = load_audio_data_async(&sources, settings, Some(&signal), |progress| {
+            if !signal.aborted() {
+                on_progress(progress)
+            }
+        })
+        .await;
+        if !signal.aborted() {
+            callback(result)
+        }
+    });
+    task
+}
+
+/// Loads the first of `sources` this browser can decode, falling back
+/// to the next one if decoding fails, then measures its loudness.
 pub async fn load_audio_data_async(
-    url: &'static str,
-    callback: impl FnOnce(u32, Vec<Frame>),
-) -> Result<JsValue, JsValue> {
-    let audio_ctx = AudioContext::new()?;
+    sources: &[AudioSource],
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<LoadedAudio, LoadError> {
+    let mut result = Err(LoadError::UnsupportedFormat);
+    for source in sources.iter().filter(|source| source.format.is_supported()) {
+        result = load_source(source, settings, signal, &mut on_progress).await;
+        match &result {
+            Err(error) if error.is_decoding_failure() => continue,
+            _ => break,
+        }
+    }
+    let (sample_rate, frames) = result?;
+
+    // Caches keep the audio as decoded, so the analysis steps are
+    // applied after reading them, and only change the audio once it is
+    // copied for kira.
+    let trimmed = if settings.trim_silence {
+        silence::leading_silence(sample_rate, &frames)
+    } else {
+        0
+    };
+    let mut loudness = Loudness::measure(sample_rate, &frames[trimmed..]);
+    if let Some(target) = settings.normalize {
+        loudness.gain = loudness.gain_to(target);
+    }
+
+    Ok(LoadedAudio {
+        sample_rate,
+        frames,
+        loudness,
+        trimmed,
+    })
+}
+
+async fn load_source(
+    source: &AudioSource,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<(u32, Rc<[Frame]>), LoadError> {
+    let url = &source.url;
+    let requested_rate = settings.resample.map(|(sample_rate, _)| sample_rate);
+    if let Some(cached) = cache::get(url, requested_rate) {
+        on_progress(LoadProgress::done());
+        return Ok(cached);
+    }
+
+    let (sample_rate, frames) = fetch_and_decode(source, settings, signal, on_progress).await?;
+    let frames: Rc<[Frame]> = frames.into();
+    cache::insert(url, requested_rate, sample_rate, frames.clone());
+    Ok((sample_rate, frames))
+}
+
+/// Reads `source` from the persistent cache, or downloads, decodes and
+/// resamples it. Formats the crate decodes itself are handled by the
+/// decoder worker, so that neither decoding nor hashing cache records
+/// blocks the page; the browser's decoders are not available in workers.
+async fn fetch_and_decode(
+    source: &AudioSource,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    on_progress: impl FnMut(LoadProgress),
+) -> Result<(u32, Vec<Frame>), LoadError> {
+    match source.format {
+        #[cfg(feature = "vorbis")]
+        AudioFormat::Ogg if !decoder_worker::in_worker() => {
+            decoder_worker::fetch_and_decode(source, settings, signal, on_progress).await
+        }
+        _ => fetch_and_decode_here(source, settings, signal, on_progress).await,
+    }
+}
+
+/// Reads `source` from the persistent cache, or downloads, decodes and
+/// resamples it, on the current thread.
+async fn fetch_and_decode_here(
+    source: &AudioSource,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<(u32, Vec<Frame>), LoadError> {
+    if let Some(version) = settings.cache_version {
+        if let Some((sample_rate, frames)) = persistent_cache::get(&source.url, version).await {
+            on_progress(LoadProgress::done());
+            return Ok(resample_to(settings, sample_rate, frames));
+        }
+    }
+
+    let encoded = download(
+        &source.url,
+        source.format.mime_type(),
+        settings,
+        signal,
+        on_progress,
+    )
+    .await?;
+    check_aborted(signal)?;
+
+    let (sample_rate, frames) = decode(source.format, &encoded).await?;
+    let (sample_rate, frames) = resample_to(settings, sample_rate, frames);
+    if let Some(version) = settings.cache_version {
+        persistent_cache::insert(&source.url, version, sample_rate, &frames);
+    }
+    Ok((sample_rate, frames))
+}
+
+async fn fetch(
+    method: &str,
+    url: &str,
+    accept: &str,
+    signal: Option<&AbortSignal>,
+) -> Result<Response, LoadError> {
+    check_aborted(signal)?;
 
     let mut opts = RequestInit::new();
-    opts.method("GET");
+    opts.method(method);
     opts.mode(RequestMode::Cors);
+    opts.signal(signal);
+
+    let request = Request::new_with_str_and_init(url, &opts).map_err(LoadError::network)?;
 
-    let request = Request::new_with_str_and_init(&url, &opts)?;
+    request
+        .headers()
+        .set("Accept", accept)
+        .map_err(LoadError::network)?;
 
-    request.headers().set("Accept", "video/ogg")?;
+    let resp: Response = JsFuture::from(Scope::current()?.fetch_with_request(&request))
+        .await
+        .and_then(|resp| resp.dyn_into())
+        .map_err(|error| match signal {
+            Some(signal) if signal.aborted() => LoadError::Aborted,
+            _ => LoadError::network(error),
+        })?;
+
+    if !resp.ok() {
+        return Err(LoadError::HttpStatus(resp.status()));
+    }
 
-    let window = web_sys::window().ok_or_else(|| JsValue::from("could not get window handle"))?;
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+    Ok(resp)
+}
 
-    let resp: Response = resp_value.dyn_into()?;
+/// Downloads the body of `url`, trying again after transient failures
+/// as allowed by `settings`.
+async fn download(
+    url: &str,
+    accept: &str,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<Vec<u8>, LoadError> {
+    let mut attempt = 0;
+    loop {
+        let result = download_once(url, accept, settings.timeout, signal, |progress| {
+            on_progress(LoadProgress {
+                attempt,
+                ..progress
+            })
+        })
+        .await;
+        match result {
+            Err(error) if error.is_transient() && attempt < settings.max_retries => {
+                sleep(settings.backoff * 2u32.saturating_pow(attempt)).await?;
+                check_aborted(signal)?;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
 
-    let encoded = JsFuture::from(resp.array_buffer()?).await?;
-    let encoded: ArrayBuffer = encoded.dyn_into()?;
+async fn download_once(
+    url: &str,
+    accept: &str,
+    timeout: Option<Duration>,
+    signal: Option<&AbortSignal>,
+    on_progress: impl FnMut(LoadProgress),
+) -> Result<Vec<u8>, LoadError> {
+    // Every attempt gets its own controller, so that a timeout only
+    // aborts this attempt, while aborting the whole load also aborts it.
+    let controller = AbortController::new().map_err(LoadError::network)?;
+    let abort = Reflect::get(&controller, &"abort".into())
+        .and_then(|abort| abort.dyn_into::<Function>())
+        .map_err(LoadError::network)?
+        .bind0(&controller);
+    if let Some(signal) = signal {
+        signal
+            .add_event_listener_with_callback("abort", &abort)
+            .map_err(LoadError::network)?;
+    }
+    let scope = Scope::current()?;
+    let timer = match timeout {
+        Some(timeout) => Some(
+            scope
+                .set_timeout(&abort, timeout)
+                .map_err(LoadError::network)?,
+        ),
+        None => None,
+    };
 
-    let decoded = JsFuture::from(audio_ctx.decode_audio_data(&encoded)?).await?;
-    let decoded: AudioBuffer = decoded.dyn_into()?;
+    let result = async {
+        let resp = fetch("GET", url, accept, Some(&controller.signal())).await?;
+        read_body(&resp, on_progress).await
+    }
+    .await;
 
-    let left = decoded.get_channel_data(0)?;
-    let right = decoded.get_channel_data(1)?;
+    if let Some(timer) = timer {
+        scope.clear_timeout(timer);
+    }
+    if let Some(signal) = signal {
+        signal
+            .remove_event_listener_with_callback("abort", &abort)
+            .ok();
+    }
 
-    let frames = left
-        .iter()
-        .zip(right.iter())
-        .map(|(&left, &right)| Frame { left, right })
-        .collect();
+    match result {
+        Err(_) if controller.signal().aborted() => {
+            check_aborted(signal)?;
+            Err(LoadError::Timeout(timeout.unwrap_or_default()))
+        }
+        result => result,
+    }
+}
 
-    callback(decoded.sample_rate() as u32, frames);
+async fn sleep(duration: Duration) -> Result<(), LoadError> {
+    let scope = Scope::current()?;
+    let promise = Promise::new(&mut |resolve, _| {
+        if scope.set_timeout(&resolve, duration).is_err() {
+            resolve.call0(&JsValue::NULL).ok();
+        }
+    });
+    JsFuture::from(promise).await.map_err(LoadError::network)?;
+    Ok(())
+}
 
-    Ok(JsValue::undefined())
+/// The global scope loads run in: the page, or the decoder worker.
+enum Scope {
+    Window(Window),
+    Worker(WorkerGlobalScope),
 }
 
-pub fn loading(content: &str) -> Html {
+impl Scope {
+    fn current() -> Result<Self, LoadError> {
+        match web_sys::window() {
+            Some(window) => Ok(Scope::Window(window)),
+            None => js_sys::global()
+                .dyn_into()
+                .map(Scope::Worker)
+                .map_err(|_| LoadError::Network("could not get global scope".into())),
+        }
+    }
+
+    fn fetch_with_request(&self, request: &Request) -> Promise {
+        match self {
+            Scope::Window(window) => window.fetch_with_request(request),
+            Scope::Worker(worker) => worker.fetch_with_request(request),
+        }
+    }
+
+    fn set_timeout(&self, callback: &Function, timeout: Duration) -> Result<i32, JsValue> {
+        let timeout = timeout.as_millis() as i32;
+        match self {
+            Scope::Window(window) => {
+                window.set_timeout_with_callback_and_timeout_and_arguments_0(callback, timeout)
+            }
+            Scope::Worker(worker) => {
+                worker.set_timeout_with_callback_and_timeout_and_arguments_0(callback, timeout)
+            }
+        }
+    }
+
+    fn clear_timeout(&self, handle: i32) {
+        match self {
+            Scope::Window(window) => window.clear_timeout_with_handle(handle),
+            Scope::Worker(worker) => worker.clear_timeout_with_handle(handle),
+        }
+    }
+
+    fn indexed_db(&self) -> Result<Option<IdbFactory>, JsValue> {
+        match self {
+            Scope::Window(window) => window.indexed_db(),
+            Scope::Worker(worker) => worker.indexed_db(),
+        }
+    }
+}
+
+fn check_aborted(signal: Option<&AbortSignal>) -> Result<(), LoadError> {
+    match signal {
+        Some(signal) if signal.aborted() => Err(LoadError::Aborted),
+        _ => Ok(()),
+    }
+}
+
+/// Resamples freshly decoded or cached audio as requested by `settings`.
+fn resample_to(settings: LoadSettings, sample_rate: u32, frames: Vec<Frame>) -> (u32, Vec<Frame>) {
+    match settings.resample {
+        Some((target_rate, quality)) if target_rate != sample_rate => (
+            target_rate,
+            resample::resample(&frames, sample_rate, target_rate, quality),
+        ),
+        _ => (sample_rate, frames),
+    }
+}
+
+async fn decode(format: AudioFormat, encoded: &[u8]) -> Result<(u32, Vec<Frame>), LoadError> {
+    match format {
+        #[cfg(feature = "vorbis")]
+        AudioFormat::Ogg => vorbis::decode(encoded),
+        _ => web_audio::decode(encoded).await,
+    }
+}
+
+/// Reads the body of `resp` chunk by chunk, reporting how many bytes
+/// were received so far.
+async fn read_body(
+    resp: &Response,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<Vec<u8>, LoadError> {
+    let mut progress = LoadProgress {
+        attempt: 0,
+        received: 0,
+        total: resp
+            .headers()
+            .get("Content-Length")
+            .ok()
+            .flatten()
+            .and_then(|length| length.parse().ok()),
+    };
+    on_progress(progress);
+
+    let mut chunks = BodyChunks::new(resp)?;
+    let mut bytes = Vec::with_capacity(progress.total.unwrap_or(0) as usize);
+    while let Some(chunk) = chunks.next().await? {
+        bytes.extend_from_slice(&chunk);
+        progress.received = bytes.len() as u64;
+        on_progress(progress);
+    }
+
+    Ok(bytes)
+}
+
+/// The body of a response, read chunk by chunk as it arrives.
+struct BodyChunks {
+    resp: Response,
+    /// The reader of the body stream and its `read` method, or `None`
+    /// if the browser does not expose the body as a stream, in which
+    /// case the whole body is read as a single chunk.
+    reader: Option<(JsValue, Function)>,
+    done: bool,
+}
+
+impl BodyChunks {
+    fn new(resp: &Response) -> Result<Self, LoadError> {
+        let reader = match resp.body() {
+            Some(body) => {
+                let reader = body.get_reader();
+                let read: Function = Reflect::get(&reader, &"read".into())
+                    .and_then(|read| read.dyn_into())
+                    .map_err(LoadError::network)?;
+                Some((reader.into(), read))
+            }
+            None => None,
+        };
+        Ok(Self {
+            resp: resp.clone(),
+            reader,
+            done: false,
+        })
+    }
+
+    async fn next(&mut self) -> Result<Option<Vec<u8>>, LoadError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let (reader, read) = match &self.reader {
+            Some(reader) => reader,
+            None => {
+                self.done = true;
+                let encoded: ArrayBuffer =
+                    JsFuture::from(self.resp.array_buffer().map_err(LoadError::network)?)
+                        .await
+                        .and_then(|encoded| encoded.dyn_into())
+                        .map_err(LoadError::network)?;
+                return Ok(Some(Uint8Array::new(&encoded).to_vec()));
+            }
+        };
+
+        let chunk = read
+            .call0(reader)
+            .and_then(|promise| promise.dyn_into::<Promise>())
+            .map_err(LoadError::network)?;
+        let chunk = JsFuture::from(chunk).await.map_err(LoadError::network)?;
+
+        if Reflect::get(&chunk, &"done".into())
+            .map_err(LoadError::network)?
+            .is_truthy()
+        {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let value: Uint8Array = Reflect::get(&chunk, &"value".into())
+            .and_then(|value| value.dyn_into())
+            .map_err(LoadError::network)?;
+        Ok(Some(value.to_vec()))
+    }
+}
+
+/// Renders the loading screen, or the failure screen if `failure`
+/// names an asset (or the url of a manifest) that could not be loaded.
+fn loading(
+    content: &str,
+    progress: &AssetProgress,
+    failure: Option<(&str, &LoadError)>,
+    retry: Callback<MouseEvent>,
+) -> Html {
+    match failure {
+        None => html! {
+            <>
+                <div class="container title">
+                    {("Loading ").to_string() + content + "..."}
+                </div>
+                { progress.view() }
+                <RouterButton<AppRoute> classes="centered" route=AppRoute::Index>
+                    { "Cancel" }
+                </RouterButton<AppRoute>>
+            </>
+        },
+        Some((asset, error)) => failure(
+            &("Could not load ".to_string() + content),
+            &format!("{}: {}", asset, error),
+            retry,
+        ),
+    }
+}
+
+/// Renders a screen saying that `title` failed because of `error`, with
+/// buttons to retry or go back to the demo list.
+fn failure(title: &str, error: &str, retry: Callback<MouseEvent>) -> Html {
     html! {
         <>
             <div class="container title">
-                {("Loading ").to_string() + content + "..."}
+                { title }
+            </div>
+            <div class="error-text centered">
+                { error }
+            </div>
+            <div class="container">
+                <div class="button-panel">
+                    <button onclick=retry>
+                        { "Retry" }
+                    </button>
+                    <RouterButton<AppRoute> route=AppRoute::Index>
+                        { "Back" }
+                    </RouterButton<AppRoute>>
+                </div>
             </div>
-            <RouterButton<AppRoute> classes="centered" route=AppRoute::Index>
-                { "Cancel" }
-            </RouterButton<AppRoute>>
         </>
     }
 }
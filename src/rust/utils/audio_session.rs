@@ -0,0 +1,219 @@
+use super::{teardown, web_audio};
+use kira::manager::AudioManager;
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    fmt,
+    rc::Rc,
+};
+use yew::{html, Callback, Html, MouseEvent};
+
+thread_local! {
+    /// The manager shared by every demo, created on first use, so that
+    /// switching demos does not open a new output stream every time.
+    ///
+    /// The audio context the manager outputs to is private to it, so it
+    /// cannot be resumed if autoplay policies suspend it. The manager is
+    /// therefore only created while the browser handles a click on the
+    /// gate of [`AudioError::Locked`], or once the page already had the
+    /// user interaction that lets new contexts run.
+    static MANAGER: RefCell<Option<Rc<RefCell<AudioManager>>>> = RefCell::new(None);
+    /// Whether the user clicked the gate. Resuming a suspended context
+    /// takes a while, so its state cannot be relied on until then.
+    static UNLOCKED: Cell<bool> = Cell::new(false);
+    static MASTER_VOLUME: Cell<f64> = Cell::new(1.0);
+    static MUTED: Cell<bool> = Cell::new(false);
+    /// What stops the mounted demo's playback, and the number of the
+    /// session it was registered by.
+    static STOP: RefCell<Option<(u64, Callback<()>)>> = RefCell::new(None);
+    static SESSION_COUNT: Cell<u64> = Cell::new(0);
+}
+
+/// The volume of the main track, from 0 to 1, which the transport bar
+/// controls for every demo.
+pub fn master_volume() -> f64 {
+    MASTER_VOLUME.with(Cell::get)
+}
+
+pub fn is_muted() -> bool {
+    MUTED.with(Cell::get)
+}
+
+/// Sets the volume of the main track, for the mounted demo and the ones
+/// mounted later.
+pub fn set_master_volume(volume: f64, muted: bool) {
+    MASTER_VOLUME.with(|master_volume| master_volume.set(volume));
+    MUTED.with(|is_muted| is_muted.set(muted));
+    MANAGER.with(|shared| {
+        if let Some(manager) = &*shared.borrow() {
+            apply_master_volume(&mut manager.borrow_mut());
+        }
+    });
+}
+
+fn create_manager() -> Result<Rc<RefCell<AudioManager>>, AudioError> {
+    let mut manager = AudioManager::new(Default::default())
+        .map_err(|error| AudioError::Unavailable(error.to_string()))?;
+    apply_master_volume(&mut manager);
+    Ok(Rc::new(RefCell::new(manager)))
+}
+
+/// Runs while the browser handles a click on the gate, which is the
+/// only time a new audio context is sure to be allowed to run.
+fn unlock() {
+    web_audio::unlock();
+    UNLOCKED.with(|unlocked| unlocked.set(true));
+    MANAGER.with(|shared| {
+        let mut shared = shared.borrow_mut();
+        if shared.is_none() {
+            // If this fails, the next session reports why.
+            *shared = create_manager().ok();
+        }
+    });
+}
+
+fn apply_master_volume(manager: &mut AudioManager) {
+    let volume = if is_muted() { 0.0 } else { master_volume() };
+    manager.main_track().set_volume(volume).ok();
+}
+
+/// Stops whatever the mounted demo is playing, if it registered a way
+/// to with [`AudioSession::on_stop`].
+pub fn stop_everything() {
+    let stop = STOP.with(|stop| stop.borrow().as_ref().map(|(_, stop)| stop.clone()));
+    if let Some(stop) = stop {
+        stop.emit(());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioError {
+    /// The browser does not let the page play audio until the user
+    /// interacts with it.
+    Locked,
+    /// The audio manager could not be created, usually because there is
+    /// no audio output device.
+    Unavailable(String),
+    /// The audio manager refused a track, parameter, group or metronome
+    /// a demo needs, usually because it has no capacity left for them.
+    Rejected(String),
+}
+
+impl AudioError {
+    pub fn rejected(error: impl fmt::Display) -> Self {
+        AudioError::Rejected(error.to_string())
+    }
+
+    /// Renders an error screen for `content`, which could not be
+    /// started because of this error. For [`AudioError::Locked`], this
+    /// is a prompt to enable audio, which calls `retry` once it is.
+    pub fn view(&self, content: &str, retry: Callback<MouseEvent>) -> Html {
+        if let AudioError::Locked = self {
+            let enable = retry.reform(|event| {
+                unlock();
+                event
+            });
+            return html! {
+                <div class="audio-gate">
+                    <div class="container title">
+                        { content }
+                    </div>
+                    <button class="centered" onclick=enable>
+                        { "Click to enable audio" }
+                    </button>
+                    <div class="demo-description centered">
+                        { "Your browser only lets pages play sound once you interact with them." }
+                    </div>
+                </div>
+            };
+        }
+        super::failure(
+            &("Could not start ".to_string() + content),
+            &self.to_string(),
+            retry,
+        )
+    }
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioError::Locked => write!(f, "audio is disabled until the page is clicked"),
+            AudioError::Unavailable(reason) => {
+                write!(f, "no audio output is available ({})", reason)
+            }
+            AudioError::Rejected(reason) => {
+                write!(f, "the audio manager is out of capacity ({})", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+/// A demo's access to the shared audio manager. Starting a session
+/// finishes tearing down the previous demo first, so that its resources
+/// are released before the next demo adds its own.
+pub struct AudioSession {
+    id: u64,
+    manager: Rc<RefCell<AudioManager>>,
+}
+
+impl AudioSession {
+    /// Fails if the shared manager does not exist yet and cannot be
+    /// created, including when the browser does not let the page play
+    /// audio yet: a manager created then would stay silent. Creating it
+    /// is attempted again on the next call.
+    pub fn start() -> Result<Self, AudioError> {
+        teardown::finish_pending();
+        MANAGER.with(|shared| {
+            let mut shared = shared.borrow_mut();
+            let manager = match &*shared {
+                Some(manager) => {
+                    // The audio thread hands back the resources removed by
+                    // the last teardown some time after their removal, so
+                    // they are only freed now.
+                    manager.borrow_mut().free_unused_resources();
+                    manager.clone()
+                }
+                None => {
+                    if !UNLOCKED.with(Cell::get) && !web_audio::is_unlocked() {
+                        return Err(AudioError::Locked);
+                    }
+                    shared.get_or_insert(create_manager()?).clone()
+                }
+            };
+            let id = SESSION_COUNT.with(|count| {
+                count.set(count.get() + 1);
+                count.get()
+            });
+            Ok(Self { id, manager })
+        })
+    }
+
+    /// Registers what [`stop_everything`] does while this session lasts.
+    pub fn on_stop(&self, stop: Callback<()>) {
+        STOP.with(|current| *current.borrow_mut() = Some((self.id, stop)));
+    }
+
+    /// Panics if the manager is already borrowed, so the borrow should
+    /// not be held across calls into other components.
+    pub fn manager(&self) -> RefMut<AudioManager> {
+        self.manager.borrow_mut()
+    }
+
+    pub(super) fn shared(&self) -> Rc<RefCell<AudioManager>> {
+        self.manager.clone()
+    }
+}
+
+impl Drop for AudioSession {
+    fn drop(&mut self) {
+        // The next demo's session may have started before this one ends.
+        STOP.with(|stop| {
+            let mut stop = stop.borrow_mut();
+            if matches!(&*stop, Some((id, _)) if *id == self.id) {
+                *stop = None;
+            }
+        });
+    }
+}
@@ -0,0 +1,505 @@
+use super::{
+    load_audio_data, loudness,
+    manifest::{self, AssetHandle, AssetKind, AssetManifest, Bindings, Manifest},
+    AssetProgress, LoadError, LoadSettings, LoadTask, LoadedAudio, Loudness,
+};
+#[cfg(feature = "vorbis")]
+use super::{
+    stream::{stream_audio, StreamingSound},
+    LoadProgress,
+};
+use kira::{
+    arrangement::handle::ArrangementHandle, instance::StopInstanceSettings, manager::AudioManager,
+    sound::handle::SoundHandle, Frame,
+};
+use serde::de::DeserializeOwned;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, hash::Hash, rc::Rc};
+use yew::{html, Callback, Html};
+
+/// The type a demo names its assets with, usually a fieldless enum.
+pub trait AssetName: Copy + Eq + Hash + Debug + DeserializeOwned + 'static {}
+
+impl<T: Copy + Eq + Hash + Debug + DeserializeOwned + 'static> AssetName for T {}
+
+/// Loads a demo's manifest and the audio data of the assets it lists.
+///
+/// The batch reports every change of its progress through `on_update`,
+/// and sends the decoded audio data of all assets at once through
+/// `on_loaded` when the last one is available.
+///
+/// Dropping the batch, usually along with the component owning it,
+/// aborts the loads in flight. Neither callback is called afterwards.
+pub struct AssetBatch<K: AssetName> {
+    state: Rc<RefCell<BatchState<K>>>,
+}
+
+struct BatchState<K> {
+    manifest_url: &'static str,
+    assets: &'static [(K, AssetKind)],
+    settings: LoadSettings,
+    manifest: Option<Manifest<K>>,
+    decoded: HashMap<K, LoadedAsset>,
+    progress: AssetProgress,
+    /// Assets that failed to load. `None` stands for the manifest, or
+    /// for the whole batch if the demo could not use what was loaded.
+    failed: Vec<(Option<K>, LoadError)>,
+    /// Dropped along with the state, which aborts the loads in flight.
+    tasks: Vec<LoadTask>,
+    on_update: Callback<()>,
+    on_loaded: Callback<LoadedAssets<K>>,
+}
+
+impl<K: AssetName> AssetBatch<K> {
+    /// Starts loading `manifest_url`, then the assets in `assets`, which
+    /// the manifest must describe with the given kinds.
+    pub fn load(
+        manifest_url: &'static str,
+        assets: &'static [(K, AssetKind)],
+        settings: LoadSettings,
+        on_update: Callback<()>,
+        on_loaded: Callback<LoadedAssets<K>>,
+    ) -> Self {
+        let state = Rc::new(RefCell::new(BatchState {
+            manifest_url,
+            assets,
+            settings,
+            manifest: None,
+            decoded: HashMap::new(),
+            progress: AssetProgress::default(),
+            failed: Vec::new(),
+            tasks: Vec::new(),
+            on_update,
+            on_loaded,
+        }));
+        load_manifest(&state);
+        Self { state }
+    }
+
+    /// Marks the whole batch as failed, for instance because the loaded
+    /// assets could not be added to the audio manager. Retrying then
+    /// reloads everything.
+    pub fn fail(&self, error: LoadError) {
+        self.state.borrow_mut().failed.push((None, error));
+    }
+
+    /// Renders the loading screen for this batch.
+    pub fn view(&self, content: &str) -> Html {
+        let state = self.state.borrow();
+        let weak_state = Rc::downgrade(&self.state);
+        super::loading(
+            content,
+            &state.progress,
+            state
+                .failed
+                .first()
+                .map(|(name, error)| (state.label(*name), error)),
+            Callback::from(move |_| {
+                if let Some(state) = weak_state.upgrade() {
+                    retry(&state);
+                }
+            }),
+        )
+    }
+}
+
+impl<K: AssetName> BatchState<K> {
+    fn label(&self, name: Option<K>) -> &str {
+        name.and_then(|name| self.manifest.as_ref()?.asset(&name))
+            .map_or(self.manifest_url, |asset| asset.label())
+    }
+
+    fn validate(&self, manifest: &Manifest<K>) -> Result<(), LoadError> {
+        for (name, kind) in self.assets {
+            match manifest.asset(name) {
+                Some(asset) if asset.sources.is_empty() => {
+                    return Err(LoadError::InvalidManifest(format!(
+                        "{:?} has no sources",
+                        name
+                    )))
+                }
+                Some(asset) if asset.kind == *kind => {}
+                Some(_) => {
+                    return Err(LoadError::InvalidManifest(format!(
+                        "{:?} should be of kind {:?}",
+                        name, kind
+                    )))
+                }
+                None => {
+                    return Err(LoadError::InvalidManifest(format!(
+                        "missing asset {:?}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What loading an asset gives, depending on its kind.
+enum LoadedAsset {
+    Audio(LoadedAudio),
+    /// A stream that already plays the start of the asset while the
+    /// rest of it downloads.
+    #[cfg(feature = "vorbis")]
+    Stream(StreamingSound),
+}
+
+impl LoadedAsset {
+    fn audio_mut(&mut self) -> Option<&mut LoadedAudio> {
+        match self {
+            LoadedAsset::Audio(audio) => Some(audio),
+            #[cfg(feature = "vorbis")]
+            LoadedAsset::Stream(_) => None,
+        }
+    }
+}
+
+fn notify<K: AssetName>(state: &Rc<RefCell<BatchState<K>>>) {
+    // The callback may run the component's `update` right away, which
+    // must be able to borrow the state.
+    let on_update = state.borrow().on_update.clone();
+    on_update.emit(());
+}
+
+fn load_manifest<K: AssetName>(state: &Rc<RefCell<BatchState<K>>>) {
+    let (url, settings) = {
+        let state = state.borrow();
+        (state.manifest_url, state.settings)
+    };
+    let weak_state = Rc::downgrade(state);
+    let task = manifest::load_manifest(url, settings, move |result| {
+        let state = match weak_state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let result = result.and_then(|manifest| {
+            state.borrow().validate(&manifest)?;
+            Ok(manifest)
+        });
+        match result {
+            Ok(manifest) => {
+                let assets = {
+                    let mut state = state.borrow_mut();
+                    state.progress = AssetProgress::new(
+                        state
+                            .assets
+                            .iter()
+                            .filter_map(|(name, _)| manifest.asset(name))
+                            .map(|asset| asset.label().to_string()),
+                    );
+                    state.settings = state.settings.persistent_cache(manifest.hash);
+                    state.manifest = Some(manifest);
+                    state.assets
+                };
+                for (name, _) in assets {
+                    load_asset(&state, *name);
+                }
+            }
+            Err(error) => state.borrow_mut().failed.push((None, error)),
+        }
+        notify(&state);
+    });
+    state.borrow_mut().tasks.push(task);
+}
+
+fn load_asset<K: AssetName>(state: &Rc<RefCell<BatchState<K>>>, name: K) {
+    let (label, kind, sources, settings) = {
+        let state = state.borrow();
+        match state
+            .manifest
+            .as_ref()
+            .and_then(|manifest| manifest.asset(&name))
+        {
+            Some(asset) => (
+                asset.label().to_string(),
+                asset.kind,
+                asset.sources.clone(),
+                // The batch normalizes its assets together once they are
+                // all loaded.
+                LoadSettings {
+                    trim_silence: asset.trim_silence,
+                    normalize: None,
+                    ..state.settings
+                },
+            ),
+            None => return,
+        }
+    };
+
+    let progress_state = Rc::downgrade(state);
+    let loaded_state = Rc::downgrade(state);
+    let task = match kind {
+        #[cfg(feature = "vorbis")]
+        AssetKind::Stream => stream_audio(sources, move |result| {
+            if let Some(state) = loaded_state.upgrade() {
+                // Streams are ready as soon as they start, so their
+                // download is not tracked.
+                state
+                    .borrow_mut()
+                    .progress
+                    .update(&label, LoadProgress::done());
+                finish_asset(&state, name, result.map(LoadedAsset::Stream));
+            }
+        }),
+        AssetKind::Sound | AssetKind::Loop => load_audio_data(
+            sources,
+            settings,
+            move |progress| {
+                if let Some(state) = progress_state.upgrade() {
+                    state.borrow_mut().progress.update(&label, progress);
+                    notify(&state);
+                }
+            },
+            move |result| {
+                if let Some(state) = loaded_state.upgrade() {
+                    finish_asset(&state, name, result.map(LoadedAsset::Audio));
+                }
+            },
+        ),
+    };
+    state.borrow_mut().tasks.push(task);
+}
+
+fn finish_asset<K: AssetName>(
+    state: &Rc<RefCell<BatchState<K>>>,
+    name: K,
+    result: Result<LoadedAsset, LoadError>,
+) {
+    let mut borrowed = state.borrow_mut();
+    match result {
+        Ok(decoded) => {
+            borrowed.decoded.insert(name, decoded);
+        }
+        Err(error) => borrowed.failed.push((Some(name), error)),
+    }
+
+    let complete = borrowed.failed.is_empty()
+        && borrowed
+            .assets
+            .iter()
+            .all(|(name, _)| borrowed.decoded.contains_key(name));
+    if !complete {
+        drop(borrowed);
+        notify(state);
+        return;
+    }
+
+    let loaded = borrowed.manifest.take().map(|manifest| {
+        let mut decoded = std::mem::take(&mut borrowed.decoded);
+        if let Some(target) = borrowed.settings.normalize {
+            normalize(&mut decoded, target);
+        }
+        LoadedAssets { manifest, decoded }
+    });
+    let on_loaded = borrowed.on_loaded.clone();
+    drop(borrowed);
+    if let Some(loaded) = loaded {
+        on_loaded.emit(loaded);
+    }
+}
+
+/// Gives every asset the gain that brings the loudest of them to the
+/// `target` loudness, so that they keep their balance when layered.
+fn normalize<K>(decoded: &mut HashMap<K, LoadedAsset>, target: f64) {
+    let gain = loudness::common_gain(
+        decoded
+            .values_mut()
+            .filter_map(LoadedAsset::audio_mut)
+            .map(|audio| audio.loudness),
+        target,
+    );
+    for audio in decoded.values_mut().filter_map(LoadedAsset::audio_mut) {
+        audio.loudness.gain = gain;
+    }
+}
+
+fn retry<K: AssetName>(state: &Rc<RefCell<BatchState<K>>>) {
+    let failed = std::mem::take(&mut state.borrow_mut().failed);
+    let restart =
+        state.borrow().manifest.is_none() || failed.iter().any(|(name, _)| name.is_none());
+    if restart {
+        {
+            let mut state = state.borrow_mut();
+            state.manifest = None;
+            state.decoded.clear();
+            state.tasks.clear();
+        }
+        load_manifest(state);
+    } else {
+        for (name, _) in failed {
+            if let Some(name) = name {
+                load_asset(state, name);
+            }
+        }
+    }
+    notify(state);
+}
+
+/// The decoded audio data of every asset of a batch, and the streams
+/// of the streamed ones.
+pub struct LoadedAssets<K> {
+    manifest: Manifest<K>,
+    decoded: HashMap<K, LoadedAsset>,
+}
+
+impl<K: AssetName> LoadedAssets<K> {
+    pub fn manifest(&self) -> &Manifest<K> {
+        &self.manifest
+    }
+
+    /// Adds every asset to `manager` as described by the manifest. If
+    /// one of them cannot be added, the ones added before it are removed
+    /// again, since the audio manager is shared between demos and would
+    /// keep them otherwise.
+    pub fn build(
+        self,
+        manager: &mut AudioManager,
+        bindings: &Bindings,
+    ) -> Result<AssetHandles<K>, LoadError> {
+        let LoadedAssets {
+            manifest,
+            mut decoded,
+        } = self;
+        let mut built = AssetHandles {
+            handles: HashMap::new(),
+            loudness: Vec::new(),
+            warnings: Vec::new(),
+            memory: 0,
+        };
+        // Going through the manifest keeps the loudness measurements in
+        // the order the manifest lists the assets in.
+        for asset in &manifest.assets {
+            let loaded = match decoded.remove(&asset.name) {
+                Some(loaded) => loaded,
+                None => continue,
+            };
+            if let Err(error) = built.add(&manifest, asset, loaded, manager, bindings) {
+                built.unload(manager);
+                return Err(error);
+            }
+        }
+        Ok(built)
+    }
+}
+
+/// The handles of every asset of a batch, once added to the audio manager.
+pub struct AssetHandles<K> {
+    handles: HashMap<K, AssetHandle>,
+    /// The loudness of every asset, along with its label, which
+    /// [`AssetHandles::view_analysis`] shows to the user.
+    loudness: Vec<(String, Loudness)>,
+    /// Problems found while analyzing the assets, which do not prevent
+    /// them from playing.
+    warnings: Vec<String>,
+    /// The size of the PCM data of every asset, in bytes.
+    memory: usize,
+}
+
+impl<K: AssetName> AssetHandles<K> {
+    fn add(
+        &mut self,
+        manifest: &Manifest<K>,
+        asset: &AssetManifest<K>,
+        loaded: LoadedAsset,
+        manager: &mut AudioManager,
+        bindings: &Bindings,
+    ) -> Result<(), LoadError> {
+        let audio = match loaded {
+            LoadedAsset::Audio(audio) => audio,
+            #[cfg(feature = "vorbis")]
+            LoadedAsset::Stream(sound) => {
+                let handle = manifest.build_stream(&asset.name, sound, manager, bindings)?;
+                self.handles.insert(asset.name, handle);
+                return Ok(());
+            }
+        };
+        let frames = audio.to_frames();
+        if let Some(mut warning) =
+            manifest.check_length(&asset.name, audio.sample_rate, frames.len())
+        {
+            if audio.trimmed > 0 {
+                warning += &format!(
+                    ", after trimming {} frames of leading silence",
+                    audio.trimmed
+                );
+            }
+            web_sys::console::warn_1(&warning.clone().into());
+            self.warnings.push(warning);
+        }
+        let memory = frames.len() * std::mem::size_of::<Frame>();
+        let handle = manifest.build(&asset.name, audio.sample_rate, frames, manager, bindings)?;
+        self.handles.insert(asset.name, handle);
+        self.loudness
+            .push((asset.label().to_string(), audio.loudness));
+        self.memory += memory;
+        Ok(())
+    }
+
+    /// Panics if `name` is not a sound. Batches check that the manifest
+    /// agrees with the kinds they were given, so this only happens if
+    /// `name` was not part of the batch.
+    pub fn sound(&self, name: K) -> &SoundHandle {
+        match self.handles.get(&name) {
+            Some(AssetHandle::Sound(handle)) => handle,
+            _ => panic!("{:?} is not a loaded sound", name),
+        }
+    }
+
+    /// Panics if `name` is not a loop. See [`AssetHandles::sound`].
+    pub fn arrangement(&self, name: K) -> &ArrangementHandle {
+        match self.handles.get(&name) {
+            Some(AssetHandle::Loop(handle, _)) => handle,
+            _ => panic!("{:?} is not a loaded loop", name),
+        }
+    }
+
+    /// Stops every instance of every asset. Streams cannot be stopped,
+    /// only removed by [`AssetHandles::unload`].
+    pub fn stop(&mut self, settings: StopInstanceSettings) {
+        for handle in self.handles.values_mut() {
+            match handle {
+                AssetHandle::Sound(sound) => sound.stop(settings).ok(),
+                AssetHandle::Loop(arrangement, _) => arrangement.stop(settings).ok(),
+                #[cfg(feature = "vorbis")]
+                AssetHandle::Stream(_) => None,
+            };
+        }
+    }
+
+    /// Removes every asset from `manager`, and returns the size of the
+    /// PCM data this releases, in bytes. The decoded audio cache keeps
+    /// its own copy, so that coming back to the demo is instant, and
+    /// the manager only hands its copy back once it runs
+    /// [`AudioManager::free_unused_resources`].
+    pub fn unload(self, manager: &mut AudioManager) -> usize {
+        for (_, handle) in self.handles {
+            match handle {
+                AssetHandle::Sound(sound) => {
+                    manager.remove_sound(sound.id()).ok();
+                }
+                AssetHandle::Loop(arrangement, sound) => {
+                    manager.remove_arrangement(arrangement.id()).ok();
+                    manager.remove_sound(sound.id()).ok();
+                }
+                #[cfg(feature = "vorbis")]
+                AssetHandle::Stream(stream) => {
+                    manager.remove_stream(stream).ok();
+                }
+            }
+        }
+        self.memory
+    }
+
+    /// Renders the loudness of every asset and the analysis warnings.
+    pub fn view_analysis(&self) -> Html {
+        html! {
+            <div class="loudness-list centered">
+                { for self.loudness.iter().map(|(label, loudness)| loudness.view(label)) }
+                { for self.warnings.iter().map(|warning| html! {
+                    <div class="warning-text">{ warning }</div>
+                }) }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use kira::Frame;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// How much decoded audio data the cache keeps, in bytes. Past that,
+/// the assets that were cached first are evicted.
+const CAPACITY: usize = 128 * 1024 * 1024;
+
+struct DecodedAudio {
+    url: String,
+    /// The sample rate the audio was requested at, or `None` if it is
+    /// kept at the rate it was decoded at. Audio is cached once per
+    /// requested rate, so that resampled audio is never resampled again.
+    requested_rate: Option<u32>,
+    sample_rate: u32,
+    frames: Rc<[Frame]>,
+}
+
+impl DecodedAudio {
+    fn size(&self) -> usize {
+        self.frames.len() * std::mem::size_of::<Frame>()
+    }
+}
+
+thread_local! {
+    /// Oldest first.
+    static DECODED_AUDIO: RefCell<VecDeque<DecodedAudio>> = RefCell::new(VecDeque::new());
+}
+
+/// Returns the audio data previously loaded from `url` at
+/// `requested_rate`, which is shared with the cache rather than copied.
+pub fn get(url: &str, requested_rate: Option<u32>) -> Option<(u32, Rc<[Frame]>)> {
+    DECODED_AUDIO.with(|cache| {
+        cache
+            .borrow()
+            .iter()
+            .find(|decoded| decoded.url == url && decoded.requested_rate == requested_rate)
+            .map(|decoded| (decoded.sample_rate, decoded.frames.clone()))
+    })
+}
+
+pub fn insert(url: &str, requested_rate: Option<u32>, sample_rate: u32, frames: Rc<[Frame]>) {
+    DECODED_AUDIO.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.retain(|decoded| decoded.url != url || decoded.requested_rate != requested_rate);
+        cache.push_back(DecodedAudio {
+            url: url.to_string(),
+            requested_rate,
+            sample_rate,
+            frames,
+        });
+        // The asset just inserted is kept even if it is larger than the
+        // whole cache, since it is about to be used.
+        let mut size: usize = cache.iter().map(DecodedAudio::size).sum();
+        while size > CAPACITY && cache.len() > 1 {
+            if let Some(evicted) = cache.pop_front() {
+                size -= evicted.size();
+            }
+        }
+    })
+}
@@ -0,0 +1,197 @@
+use super::LoadError;
+use kira::Frame;
+
+const MINUS_3_DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// The speaker layouts we know how to fold down to stereo.
+///
+/// Channel ordering and downmix coefficients follow the "speakers"
+/// channel interpretation of the Web Audio API, which is also the
+/// order in which `AudioBuffer` exposes its channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    /// Left, right, surround left, surround right.
+    Quad,
+    /// Left, right, center, LFE, surround left, surround right.
+    FivePointOne,
+}
+
+impl ChannelLayout {
+    pub fn from_channel_count(channels: u32) -> Result<Self, LoadError> {
+        match channels {
+            1 => Ok(ChannelLayout::Mono),
+            2 => Ok(ChannelLayout::Stereo),
+            4 => Ok(ChannelLayout::Quad),
+            6 => Ok(ChannelLayout::FivePointOne),
+            _ => Err(LoadError::UnsupportedChannelLayout(channels)),
+        }
+    }
+
+    pub fn channel_count(self) -> usize {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Quad => 4,
+            ChannelLayout::FivePointOne => 6,
+        }
+    }
+
+    /// Mixes one sample of every channel down to a stereo frame.
+    pub fn downmix(self, samples: &[f32]) -> Frame {
+        match self {
+            ChannelLayout::Mono => Frame {
+                left: samples[0],
+                right: samples[0],
+            },
+            ChannelLayout::Stereo => Frame {
+                left: samples[0],
+                right: samples[1],
+            },
+            ChannelLayout::Quad => Frame {
+                left: 0.5 * (samples[0] + samples[2]),
+                right: 0.5 * (samples[1] + samples[3]),
+            },
+            // The LFE channel is dropped, as the Web Audio API does.
+            ChannelLayout::FivePointOne => Frame {
+                left: samples[0] + MINUS_3_DB * (samples[2] + samples[4]),
+                right: samples[1] + MINUS_3_DB * (samples[2] + samples[5]),
+            },
+        }
+    }
+
+    /// Converts planar channel data, laid out in this layout's
+    /// channel order, into stereo frames.
+    pub fn to_frames(self, channels: &[Vec<f32>]) -> Vec<Frame> {
+        debug_assert_eq!(channels.len(), self.channel_count());
+
+        let length = channels.iter().map(Vec::len).min().unwrap_or(0);
+        let mut samples = vec![0.0; channels.len()];
+        (0..length)
+            .map(|i| {
+                for (sample, channel) in samples.iter_mut().zip(channels) {
+                    *sample = channel[i];
+                }
+                self.downmix(&samples)
+            })
+            .collect()
+    }
+}
+
+/// Lays out stereo frames as a flat list of samples, alternating
+/// between the left and right channels.
+pub fn interleave(frames: &[Frame]) -> Vec<f32> {
+    let mut samples = Vec::with_capacity(frames.len() * 2);
+    for frame in frames {
+        samples.push(frame.left);
+        samples.push(frame.right);
+    }
+    samples
+}
+
+/// The reverse of [`interleave`]. A trailing unpaired sample is dropped.
+pub fn deinterleave(samples: &[f32]) -> Vec<Frame> {
+    samples
+        .chunks_exact(2)
+        .map(|sample| Frame {
+            left: sample[0],
+            right: sample[1],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_frame(frame: Frame, left: f32, right: f32) {
+        assert!((frame.left - left).abs() < 1e-6, "{:?}", frame);
+        assert!((frame.right - right).abs() < 1e-6, "{:?}", frame);
+    }
+
+    #[test]
+    fn channel_counts_round_trip() {
+        for &channels in &[1, 2, 4, 6] {
+            let layout = ChannelLayout::from_channel_count(channels).unwrap();
+            assert_eq!(layout.channel_count(), channels as usize);
+        }
+        for &channels in &[0, 3, 5, 8] {
+            assert!(matches!(
+                ChannelLayout::from_channel_count(channels),
+                Err(LoadError::UnsupportedChannelLayout(count)) if count == channels
+            ));
+        }
+    }
+
+    #[test]
+    fn mono_is_duplicated() {
+        assert_frame(ChannelLayout::Mono.downmix(&[0.25]), 0.25, 0.25);
+    }
+
+    #[test]
+    fn stereo_is_unchanged() {
+        assert_frame(ChannelLayout::Stereo.downmix(&[0.25, -0.5]), 0.25, -0.5);
+    }
+
+    #[test]
+    fn quad_averages_front_and_surround() {
+        assert_frame(
+            ChannelLayout::Quad.downmix(&[1.0, 0.0, 0.5, 0.0]),
+            0.75,
+            0.0,
+        );
+        assert_frame(
+            ChannelLayout::Quad.downmix(&[0.0, 1.0, 0.0, -1.0]),
+            0.0,
+            0.0,
+        );
+    }
+
+    #[test]
+    fn five_point_one_mixes_center_and_surround_at_minus_3_db() {
+        let layout = ChannelLayout::FivePointOne;
+        assert_frame(layout.downmix(&[1.0, 0.0, 0.0, 0.0, 0.0, 0.0]), 1.0, 0.0);
+        assert_frame(layout.downmix(&[0.0, 1.0, 0.0, 0.0, 0.0, 0.0]), 0.0, 1.0);
+        assert_frame(
+            layout.downmix(&[0.0, 0.0, 1.0, 0.0, 0.0, 0.0]),
+            MINUS_3_DB,
+            MINUS_3_DB,
+        );
+        assert_frame(layout.downmix(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]), 0.0, 0.0);
+        assert_frame(
+            layout.downmix(&[0.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+            MINUS_3_DB,
+            0.0,
+        );
+        assert_frame(
+            layout.downmix(&[0.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
+            0.0,
+            MINUS_3_DB,
+        );
+    }
+
+    #[test]
+    fn to_frames_stops_at_the_shortest_channel() {
+        let frames = ChannelLayout::Stereo.to_frames(&[vec![0.1, 0.2, 0.3], vec![-0.1, -0.2]]);
+        assert_eq!(frames.len(), 2);
+        assert_frame(frames[0], 0.1, -0.1);
+        assert_frame(frames[1], 0.2, -0.2);
+
+        let frames = ChannelLayout::Mono.to_frames(&[vec![0.5, -0.5]]);
+        assert_eq!(frames.len(), 2);
+        assert_frame(frames[1], -0.5, -0.5);
+    }
+
+    #[test]
+    fn interleaving_round_trips() {
+        let frames = ChannelLayout::Stereo.to_frames(&[vec![0.1, 0.2], vec![-0.1, -0.2]]);
+        let samples = interleave(&frames);
+        assert_eq!(samples, vec![0.1, -0.1, 0.2, -0.2]);
+        let restored = deinterleave(&samples);
+        assert_eq!(restored.len(), 2);
+        assert_frame(restored[1], 0.2, -0.2);
+        // An unpaired sample is dropped.
+        assert_eq!(deinterleave(&[0.1, -0.1, 0.2]).len(), 1);
+    }
+}
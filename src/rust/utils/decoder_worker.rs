@@ -0,0 +1,373 @@
+use super::{
+    channels::{deinterleave, interleave},
+    AudioSource, LoadError, LoadProgress, LoadSettings, LoadTask,
+};
+use js_sys::{Array, Float32Array, Function, Object, Promise, Reflect};
+use kira::Frame;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AbortSignal, DedicatedWorkerGlobalScope, Event, MessageEvent, Worker};
+
+/// The script the worker is started from, built by webpack from
+/// `src/decoder_worker.js`.
+const WORKER_SCRIPT: &str = "/decoder_worker.js";
+
+/// A message from the page to the worker.
+#[derive(Serialize, Deserialize)]
+enum DecodeRequest {
+    Decode {
+        source: AudioSource,
+        settings: LoadSettings,
+    },
+    /// Aborts the request, which the page gave up on.
+    Abort,
+}
+
+/// A message from the worker to the page.
+#[derive(Serialize, Deserialize)]
+enum DecodeResponse {
+    Progress(LoadProgress),
+    /// The decoded (and resampled) audio. Its samples, with both
+    /// channels interleaved, are transferred along with the message as
+    /// a `Float32Array` rather than copied.
+    Decoded {
+        sample_rate: u32,
+    },
+    Failed(LoadError),
+}
+
+/// Packs a message between the page and the worker as an object with
+/// the id of the request it belongs to.
+fn pack(id: u32, message: &impl Serialize) -> Object {
+    let object = Object::new();
+    let message = serde_json::to_string(message).expect("could not serialize a decoder message");
+    Reflect::set(&object, &"id".into(), &id.into()).ok();
+    Reflect::set(&object, &"message".into(), &message.into()).ok();
+    object
+}
+
+fn unpack<T: DeserializeOwned>(data: &JsValue) -> Option<(u32, T)> {
+    let id = Reflect::get(data, &"id".into()).ok()?.as_f64()? as u32;
+    let message = Reflect::get(data, &"message".into()).ok()?.as_string()?;
+    Some((id, serde_json::from_str(&message).ok()?))
+}
+
+/// Handles the requests of the page, in the decoder worker. Dropping
+/// the task of a request aborts its download.
+pub fn run_worker() {
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let tasks: Rc<RefCell<HashMap<u32, LoadTask>>> = Rc::default();
+    let on_message = Closure::wrap(Box::new({
+        let scope = scope.clone();
+        move |event: MessageEvent| match unpack(&event.data()) {
+            Some((id, DecodeRequest::Decode { source, settings })) => {
+                let task = LoadTask::new();
+                let signal = task.signal();
+                tasks.borrow_mut().insert(id, task);
+                let tasks = tasks.clone();
+                let scope = scope.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let result = super::fetch_and_decode_here(
+                        &source,
+                        settings,
+                        Some(&signal),
+                        |progress| {
+                            if !signal.aborted() {
+                                respond(&scope, id, &DecodeResponse::Progress(progress), None)
+                            }
+                        },
+                    )
+                    .await;
+                    tasks.borrow_mut().remove(&id);
+                    if signal.aborted() {
+                        return;
+                    }
+                    match result {
+                        Ok((sample_rate, frames)) => respond(
+                            &scope,
+                            id,
+                            &DecodeResponse::Decoded { sample_rate },
+                            Some(Float32Array::from(interleave(&frames).as_slice())),
+                        ),
+                        Err(error) => respond(&scope, id, &DecodeResponse::Failed(error), None),
+                    }
+                });
+            }
+            Some((id, DecodeRequest::Abort)) => {
+                tasks.borrow_mut().remove(&id);
+            }
+            None => {}
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The worker handles messages for as long as it runs.
+    on_message.forget();
+}
+
+fn respond(
+    scope: &DedicatedWorkerGlobalScope,
+    id: u32,
+    response: &DecodeResponse,
+    samples: Option<Float32Array>,
+) {
+    let message = pack(id, response);
+    let transfer = Array::new();
+    if let Some(samples) = samples {
+        Reflect::set(&message, &"samples".into(), &samples).ok();
+        transfer.push(&samples.buffer());
+    }
+    scope.post_message_with_transfer(&message, &transfer).ok();
+}
+
+/// Whether this code runs in the decoder worker rather than in the page.
+pub fn in_worker() -> bool {
+    web_sys::window().is_none()
+}
+
+enum WorkerState {
+    NotStarted,
+    Running(Connection),
+    /// The worker could not start, or crashed. Assets are decoded on
+    /// the page from then on.
+    Failed,
+}
+
+/// The page's end of the decoder worker.
+struct Connection {
+    worker: Worker,
+    /// The requests the worker is handling, by id.
+    requests: HashMap<u32, Rc<RefCell<Mailbox>>>,
+    next_id: u32,
+}
+
+thread_local! {
+    /// The worker is started by the first request, and shared by all
+    /// the following ones.
+    static WORKER: RefCell<WorkerState> = RefCell::new(WorkerState::NotStarted);
+}
+
+impl Connection {
+    fn start() -> Result<Self, JsValue> {
+        let worker = Worker::new(WORKER_SCRIPT)?;
+        let on_message = Closure::wrap(Box::new(|event: MessageEvent| {
+            let data = event.data();
+            // Sent by `src/decoder_worker.js` if the module cannot load.
+            if let Some(reason) = Reflect::get(&data, &"failed".into())
+                .ok()
+                .and_then(|reason| reason.as_string())
+            {
+                fail(&reason);
+                return;
+            }
+            let (id, response) = match unpack(&data) {
+                Some(unpacked) => unpacked,
+                None => return,
+            };
+            let samples = Reflect::get(&data, &"samples".into())
+                .ok()
+                .and_then(|samples| samples.dyn_into().ok());
+            let mailbox = WORKER.with(|worker| match &*worker.borrow() {
+                WorkerState::Running(connection) => connection.requests.get(&id).cloned(),
+                _ => None,
+            });
+            if let Some(mailbox) = mailbox {
+                Mailbox::deliver(&mailbox, Delivery::Response(response, samples));
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        // Fired if the script cannot be loaded, or if the worker panics.
+        let on_error = Closure::wrap(Box::new(|event: Event| {
+            let reason = Reflect::get(&event, &"message".into())
+                .ok()
+                .and_then(|reason| reason.as_string())
+                .unwrap_or_else(|| "could not load the worker script".into());
+            fail(&reason);
+        }) as Box<dyn FnMut(Event)>);
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        // The worker is never dropped, so neither are its handlers.
+        on_message.forget();
+        on_error.forget();
+        Ok(Self {
+            worker,
+            requests: HashMap::new(),
+            next_id: 0,
+        })
+    }
+}
+
+/// Sends a request to the worker, starting it if needed. Returns the id
+/// of the request, or `None` if the worker is not available.
+fn send(request: DecodeRequest, mailbox: &Rc<RefCell<Mailbox>>) -> Option<u32> {
+    let sent: Result<_, JsValue> = WORKER.with(|worker| {
+        let mut worker = worker.borrow_mut();
+        if let WorkerState::NotStarted = *worker {
+            *worker = match Connection::start() {
+                Ok(connection) => WorkerState::Running(connection),
+                Err(error) => {
+                    web_sys::console::warn_2(&"could not start the decoder worker".into(), &error);
+                    WorkerState::Failed
+                }
+            };
+        }
+        let connection = match &mut *worker {
+            WorkerState::Running(connection) => connection,
+            _ => return Ok(None),
+        };
+        let id = connection.next_id;
+        connection.next_id += 1;
+        connection.worker.post_message(&pack(id, &request))?;
+        connection.requests.insert(id, mailbox.clone());
+        Ok(Some(id))
+    });
+    match sent {
+        Ok(id) => id,
+        Err(error) => {
+            fail(&super::describe_js_error(error));
+            None
+        }
+    }
+}
+
+/// Forgets request `id`, and has the worker abort it if it is still
+/// running.
+fn finish(id: u32, abort: bool) {
+    WORKER.with(|worker| {
+        if let WorkerState::Running(connection) = &mut *worker.borrow_mut() {
+            connection.requests.remove(&id);
+            if abort {
+                connection
+                    .worker
+                    .post_message(&pack(id, &DecodeRequest::Abort))
+                    .ok();
+            }
+        }
+    })
+}
+
+/// Stops using the worker, and has the requests it was handling
+/// decoded on the page instead.
+fn fail(reason: &str) {
+    web_sys::console::warn_1(
+        &format!(
+            "the decoder worker failed ({}), decoding on the page instead",
+            reason
+        )
+        .into(),
+    );
+    let state = WORKER.with(|worker| worker.replace(WorkerState::Failed));
+    if let WorkerState::Running(connection) = state {
+        connection.worker.terminate();
+        for mailbox in connection.requests.values() {
+            Mailbox::deliver(mailbox, Delivery::WorkerFailed);
+        }
+    }
+}
+
+/// Has the decoder worker download, decode and resample `source`, or
+/// does it on the page if the worker is not available.
+pub async fn fetch_and_decode(
+    source: &AudioSource,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+    mut on_progress: impl FnMut(LoadProgress),
+) -> Result<(u32, Vec<Frame>), LoadError> {
+    super::check_aborted(signal)?;
+
+    let mailbox = Rc::new(RefCell::new(Mailbox::default()));
+    let request = DecodeRequest::Decode {
+        source: source.clone(),
+        settings,
+    };
+    let id = match send(request, &mailbox) {
+        Some(id) => id,
+        None => return super::fetch_and_decode_here(source, settings, signal, on_progress).await,
+    };
+    // Aborting has to wake the loop below by itself, since the worker
+    // does not answer aborted requests.
+    let on_abort: Closure<dyn FnMut()> = Closure::once({
+        let mailbox = mailbox.clone();
+        move || Mailbox::deliver(&mailbox, Delivery::Aborted)
+    });
+    if let Some(signal) = signal {
+        signal
+            .add_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref())
+            .map_err(LoadError::network)?;
+    }
+
+    let result = loop {
+        match Mailbox::next(&mailbox).await {
+            Delivery::Response(DecodeResponse::Progress(progress), _) => on_progress(progress),
+            Delivery::Response(DecodeResponse::Decoded { sample_rate }, Some(samples)) => {
+                break Some(Ok((sample_rate, deinterleave(&samples.to_vec()))))
+            }
+            Delivery::Response(DecodeResponse::Decoded { .. }, None) => {
+                break Some(Err(LoadError::Undecodable(
+                    "the decoder worker sent no samples".into(),
+                )))
+            }
+            Delivery::Response(DecodeResponse::Failed(error), _) => break Some(Err(error)),
+            Delivery::Aborted => break Some(Err(LoadError::Aborted)),
+            Delivery::WorkerFailed => break None,
+        }
+    };
+
+    if let Some(signal) = signal {
+        signal
+            .remove_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref())
+            .ok();
+    }
+    finish(id, matches!(result, Some(Err(LoadError::Aborted))));
+    match result {
+        Some(result) => result,
+        None => super::fetch_and_decode_here(source, settings, signal, on_progress).await,
+    }
+}
+
+/// What the request loop of [`fetch_and_decode`] is woken up by.
+enum Delivery {
+    /// A response from the worker, with the samples transferred along
+    /// with it, if any.
+    Response(DecodeResponse, Option<Float32Array>),
+    Aborted,
+    /// The worker failed, and will not answer anymore.
+    WorkerFailed,
+}
+
+/// Deliveries that have not been handled yet.
+#[derive(Default)]
+struct Mailbox {
+    deliveries: VecDeque<Delivery>,
+    /// Resolves the promise the receiving loop is waiting on, if any.
+    wake: Option<Function>,
+}
+
+impl Mailbox {
+    fn deliver(mailbox: &Rc<RefCell<Self>>, delivery: Delivery) {
+        let wake = {
+            let mut mailbox = mailbox.borrow_mut();
+            mailbox.deliveries.push_back(delivery);
+            mailbox.wake.take()
+        };
+        if let Some(wake) = wake {
+            wake.call0(&JsValue::NULL).ok();
+        }
+    }
+
+    async fn next(mailbox: &Rc<RefCell<Self>>) -> Delivery {
+        loop {
+            if let Some(delivery) = mailbox.borrow_mut().deliveries.pop_front() {
+                return delivery;
+            }
+            let delivered = Promise::new(&mut |resolve, _| {
+                mailbox.borrow_mut().wake = Some(resolve);
+            });
+            JsFuture::from(delivered).await.ok();
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use super::web_audio;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// Ogg Vorbis.
+    Ogg,
+    Mp3,
+    Flac,
+    Wav,
+}
+
+impl AudioFormat {
+    /// Guesses the format of an audio file from the extension of its url.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let path = url.split(|c| c == '?' || c == '#').next().unwrap_or(url);
+        let extension = path.rsplit('.').next()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ogg" | "oga" => Some(AudioFormat::Ogg),
+            "mp3" => Some(AudioFormat::Mp3),
+            "flac" => Some(AudioFormat::Flac),
+            "wav" => Some(AudioFormat::Wav),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "audio/ogg; codecs=vorbis",
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Wav => "audio/wav",
+        }
+    }
+
+    /// Whether files in this format can be decoded, either by the
+    /// crate itself or by the browser.
+    pub fn is_supported(self) -> bool {
+        (cfg!(feature = "vorbis") && self == AudioFormat::Ogg)
+            || web_audio::can_play(self.mime_type())
+    }
+}
+
+/// One of the encodings an asset is available in. In manifests, sources
+/// are written as plain urls, and the format is guessed from the
+/// extension.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AudioSource {
+    pub url: String,
+    pub format: AudioFormat,
+}
+
+impl TryFrom<String> for AudioSource {
+    type Error = String;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        match AudioFormat::from_url(&url) {
+            Some(format) => Ok(Self { url, format }),
+            None => Err(format!("unknown audio format for \"{}\"", url)),
+        }
+    }
+}
+
+impl From<AudioSource> for String {
+    fn from(source: AudioSource) -> Self {
+        source.url
+    }
+}
@@ -0,0 +1,325 @@
+use kira::Frame;
+use std::f64::consts::PI;
+use yew::{html, Html};
+
+/// The length of a gating block, in seconds.
+const BLOCK_DURATION: f64 = 0.4;
+/// Gating blocks overlap by 75%, so a new one starts every quarter block.
+const BLOCK_STEPS: usize = 4;
+/// Blocks quieter than this, in LUFS, are ignored entirely.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks more than this many LU below the loudness of the blocks that
+/// passed the absolute gate are ignored.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Loudness measurements of an asset, taken before normalization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// The highest sample magnitude, in dBFS.
+    pub peak: f64,
+    /// The integrated loudness as defined by ITU-R BS.1770-4, in LUFS.
+    /// Negative infinity for silent assets.
+    pub integrated: f64,
+    /// The gain normalization applied to the asset, in dB. 0 if the
+    /// asset was not normalized.
+    pub gain: f64,
+}
+
+impl Loudness {
+    pub fn measure(sample_rate: u32, frames: &[Frame]) -> Self {
+        let peak = frames
+            .iter()
+            .map(|frame| frame.left.abs().max(frame.right.abs()))
+            .fold(0.0f32, f32::max);
+        Self {
+            peak: 20.0 * (peak as f64).log10(),
+            integrated: integrated_loudness(sample_rate, frames),
+            gain: 0.0,
+        }
+    }
+
+    /// The gain, in dB, that brings the asset to the `target` loudness,
+    /// reduced if needed so that the peak stays below full scale.
+    pub fn gain_to(self, target: f64) -> f64 {
+        if !self.integrated.is_finite() {
+            return 0.0;
+        }
+        (target - self.integrated).min(-self.peak)
+    }
+
+    pub fn view(self, label: &str) -> Html {
+        html! {
+            <div class="loudness-row">
+                <div class="loudness-label">{ label }</div>
+                <div class="loudness-text">{ format!(
+                    "{:.1} LUFS, peak {:.1} dBFS{}",
+                    self.integrated,
+                    self.peak,
+                    if self.gain == 0.0 {
+                        String::new()
+                    } else {
+                        format!(", normalized by {:+.1} dB", self.gain)
+                    },
+                ) }</div>
+            </div>
+        }
+    }
+}
+
+/// The gain, in dB, that brings the loudest of several assets to the
+/// `target` loudness while keeping every peak below full scale. Applying
+/// it to all of them keeps the balance they were mixed with. Silent
+/// assets are ignored, and the gain is 0 if every asset is silent.
+pub fn common_gain(measurements: impl IntoIterator<Item = Loudness>, target: f64) -> f64 {
+    let gain = measurements
+        .into_iter()
+        .filter(|loudness| loudness.integrated.is_finite())
+        .map(|loudness| loudness.gain_to(target))
+        .fold(f64::INFINITY, f64::min);
+    if gain.is_finite() {
+        gain
+    } else {
+        0.0
+    }
+}
+
+/// Applies a gain in dB to `frames`.
+pub fn apply_gain(frames: &mut [Frame], gain: f64) {
+    let factor = 10.0f64.powf(gain / 20.0) as f32;
+    for frame in frames {
+        frame.left *= factor;
+        frame.right *= factor;
+    }
+}
+
+fn integrated_loudness(sample_rate: u32, frames: &[Frame]) -> f64 {
+    let mut left = KWeighting::new(sample_rate);
+    let mut right = KWeighting::new(sample_rate);
+
+    // Sum the energy of every quarter block, so that each (overlapping)
+    // block is the sum of four consecutive quarters.
+    let step_length = ((BLOCK_DURATION * sample_rate as f64) as usize / BLOCK_STEPS).max(1);
+    let steps: Vec<f64> = frames
+        .chunks(step_length)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|frame| {
+                    let left = left.process(frame.left as f64);
+                    let right = right.process(frame.right as f64);
+                    left * left + right * right
+                })
+                .sum::<f64>()
+        })
+        .collect();
+
+    let block_length = (step_length * BLOCK_STEPS) as f64;
+    let mut blocks: Vec<f64> = steps
+        .windows(BLOCK_STEPS)
+        .map(|window| window.iter().sum::<f64>() / block_length)
+        .collect();
+    // Assets shorter than a block are measured as a single block.
+    if blocks.is_empty() && !frames.is_empty() {
+        blocks.push(steps.iter().sum::<f64>() / frames.len() as f64);
+    }
+
+    let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = blocks
+            .iter()
+            .copied()
+            .filter(|&energy| loudness(energy) > threshold)
+            .collect();
+        if gated.is_empty() {
+            None
+        } else {
+            Some(gated.iter().sum::<f64>() / gated.len() as f64)
+        }
+    };
+
+    let absolute = match gated_mean(ABSOLUTE_GATE) {
+        Some(energy) => energy,
+        None => return f64::NEG_INFINITY,
+    };
+    let relative_threshold = loudness(absolute) + RELATIVE_GATE;
+    gated_mean(relative_threshold.max(ABSOLUTE_GATE)).map_or(f64::NEG_INFINITY, loudness)
+}
+
+/// The K-weighting pre-filter of ITU-R BS.1770: a high shelf modelling
+/// the acoustic effect of the head, followed by a high-pass filter.
+/// The coefficients are derived for any sample rate, rather than using
+/// the ones the standard tabulates for 48 kHz.
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f64;
+        Self {
+            stages: [
+                Biquad::high_shelf(sample_rate, 1681.974, 3.999_844, 0.707_175),
+                Biquad::high_pass(sample_rate, 38.135_47, 0.500_327),
+            ],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        self.stages
+            .iter_mut()
+            .fold(input, |sample, stage| stage.process(sample))
+    }
+}
+
+/// A second-order filter in transposed direct form II, with
+/// coefficients normalized by `a0`.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            state: [0.0; 2],
+        }
+    }
+
+    /// The high shelf of the K-weighting filter. This is not the usual
+    /// cookbook shelf: the gain at its center frequency is tuned so
+    /// that, at 48 kHz, it gives the coefficients the standard lists.
+    fn high_shelf(sample_rate: f64, frequency: f64, gain: f64, q: f64) -> Self {
+        let k = (PI * frequency / sample_rate).tan();
+        let high_gain = 10.0f64.powf(gain / 20.0);
+        let band_gain = high_gain.powf(0.499_666_774_154_541_6);
+        Self::new(
+            [
+                high_gain + band_gain * k / q + k * k,
+                2.0 * (k * k - high_gain),
+                high_gain - band_gain * k / q + k * k,
+            ],
+            [
+                1.0 + k / q + k * k,
+                2.0 * (k * k - 1.0),
+                1.0 - k / q + k * k,
+            ],
+        )
+    }
+
+    /// The high-pass filter of the K-weighting filter. As in the
+    /// standard, only the denominator is normalized.
+    fn high_pass(sample_rate: f64, frequency: f64, q: f64) -> Self {
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Self::new(
+            [a0, -2.0 * a0, a0],
+            [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, amplitude: f32, seconds: u32) -> Vec<f32> {
+        (0..sample_rate * seconds)
+            .map(|i| amplitude * (2.0 * PI * 1000.0 * i as f64 / sample_rate as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn k_weighting_matches_the_coefficients_at_48_khz() {
+        // As listed in ITU-R BS.1770-4, tables 1 and 2.
+        let expected = [
+            (
+                [
+                    1.535_124_859_586_97,
+                    -2.691_696_189_406_38,
+                    1.198_392_810_852_85,
+                ],
+                [-1.690_659_293_182_41, 0.732_480_774_215_85],
+            ),
+            (
+                [1.0, -2.0, 1.0],
+                [-1.990_047_454_833_98, 0.990_072_250_366_21],
+            ),
+        ];
+        let filter = KWeighting::new(48000);
+        for (stage, (b, a)) in filter.stages.iter().zip(&expected) {
+            for (actual, expected) in stage.b.iter().zip(b) {
+                assert!((actual - expected).abs() < 1e-6);
+            }
+            for (actual, expected) in stage.a.iter().zip(a) {
+                assert!((actual - expected).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn sine_at_minus_20_dbfs() {
+        for &sample_rate in &[44100, 48000] {
+            let samples = sine(sample_rate, 0.1, 5);
+            let stereo: Vec<Frame> = samples
+                .iter()
+                .map(|&sample| Frame {
+                    left: sample,
+                    right: sample,
+                })
+                .collect();
+            let left_only: Vec<Frame> = samples
+                .iter()
+                .map(|&sample| Frame {
+                    left: sample,
+                    right: 0.0,
+                })
+                .collect();
+
+            let loudness = Loudness::measure(sample_rate, &stereo);
+            assert!((loudness.integrated + 20.0).abs() < 0.1, "{:?}", loudness);
+            assert!((loudness.peak + 20.0).abs() < 0.01, "{:?}", loudness);
+            // A single channel is 3 dB quieter.
+            let integrated = integrated_loudness(sample_rate, &left_only);
+            assert!((integrated + 23.01).abs() < 0.1, "{}", integrated);
+        }
+    }
+
+    #[test]
+    fn common_gain_follows_the_loudest_asset() {
+        let loudness = |integrated, peak| Loudness {
+            peak,
+            integrated,
+            gain: 0.0,
+        };
+        let quiet = loudness(-30.0, -12.0);
+        let loud = loudness(-24.0, -10.0);
+        let silent = loudness(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        assert_eq!(common_gain(vec![quiet, loud, silent], -20.0), 4.0);
+        // The loud asset would clip past +10 dB.
+        assert_eq!(common_gain(vec![quiet, loud], -10.0), 10.0);
+        assert_eq!(common_gain(vec![silent], -20.0), 0.0);
+        assert_eq!(common_gain(vec![], -20.0), 0.0);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let silence = vec![
+            Frame {
+                left: 0.0,
+                right: 0.0
+            };
+            48000
+        ];
+        assert_eq!(integrated_loudness(48000, &silence), f64::NEG_INFINITY);
+    }
+}
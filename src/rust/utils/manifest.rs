@@ -0,0 +1,388 @@
+#[cfg(feature = "vorbis")]
+use super::StreamingSound;
+use super::{AudioSource, LoadError, LoadSettings, LoadTask};
+use kira::{
+    arrangement::{handle::ArrangementHandle, Arrangement, LoopArrangementSettings},
+    group::{GroupId, GroupSet},
+    manager::AudioManager,
+    mixer::SubTrackId,
+    sound::{handle::SoundHandle, Sound, SoundSettings},
+    Frame, Tempo,
+};
+#[cfg(feature = "vorbis")]
+use kira::{audio_stream::AudioStreamId, mixer::TrackIndex};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::HashMap;
+use web_sys::AbortSignal;
+
+/// How far, in seconds, the length of an asset may be below its semantic
+/// duration (or above it, for assets of exact length) before it is
+/// reported.
+const LENGTH_TOLERANCE: f64 = 0.001;
+
+/// Describes the assets of a demo. Manifests are shipped as JSON next
+/// to the audio files, so that assets can be swapped or retimed without
+/// touching the code. `K` is the type the demo names its assets with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest<K> {
+    /// The tempo of the demo's music, in beats per minute.
+    pub tempo: f64,
+    pub assets: Vec<AssetManifest<K>>,
+    /// The hash of the manifest file, which changes whenever the file
+    /// is edited.
+    #[serde(skip)]
+    pub hash: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AssetManifest<K> {
+    pub name: K,
+    /// The encodings the asset is available in, by order of preference.
+    pub sources: Vec<AudioSource>,
+    #[serde(default)]
+    pub kind: AssetKind,
+    /// The musical length of the asset, in beats.
+    #[serde(default)]
+    pub semantic_duration: Option<f64>,
+    /// Whether the asset should last exactly its semantic duration, as
+    /// loops that repeat the whole file do. Other assets may have a tail
+    /// ringing past their semantic duration.
+    #[serde(default)]
+    pub exact_length: bool,
+    /// Whether to trim the leading silence of the asset, for encodings
+    /// that pad the start of files. Ogg Vorbis files have no such
+    /// padding once decoded.
+    #[serde(default)]
+    pub trim_silence: bool,
+    /// The name of the track the asset plays on by default, as bound
+    /// with [`Bindings::track`].
+    #[serde(default)]
+    pub track: Option<String>,
+    /// The names of the groups the asset belongs to, as bound with
+    /// [`Bindings::group`].
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssetKind {
+    /// A plain sound.
+    #[default]
+    Sound,
+    /// A sound wrapped in a looping arrangement.
+    Loop,
+    /// An Ogg Vorbis file played through an audio stream while it is
+    /// downloaded, which starts as soon as the asset is added to the
+    /// manager. Groups and semantic durations do not apply to streams.
+    #[cfg(feature = "vorbis")]
+    Stream,
+}
+
+impl<K> AssetManifest<K> {
+    /// A short name for the asset to show on the loading screen: the
+    /// file name of its first source, without extension.
+    pub fn label(&self) -> &str {
+        let url = self
+            .sources
+            .first()
+            .map_or("", |source| source.url.as_str());
+        let file_name = url.rsplit('/').next().unwrap_or(url);
+        file_name.rsplitn(2, '.').last().unwrap_or(file_name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AssetHandle {
+    Sound(SoundHandle),
+    /// The arrangement, and the sound it loops, which has to be removed
+    /// separately when unloading the asset.
+    Loop(ArrangementHandle, SoundHandle),
+    #[cfg(feature = "vorbis")]
+    Stream(AudioStreamId),
+}
+
+/// The tracks and groups a demo created, under the names its manifest
+/// refers to them by.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    tracks: HashMap<String, SubTrackId>,
+    groups: HashMap<String, GroupId>,
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(mut self, name: &str, track: impl Into<SubTrackId>) -> Self {
+        self.tracks.insert(name.to_string(), track.into());
+        self
+    }
+
+    pub fn group(mut self, name: &str, group: impl Into<GroupId>) -> Self {
+        self.groups.insert(name.to_string(), group.into());
+        self
+    }
+
+    /// The track `asset` plays on, if the manifest gives one.
+    fn resolve_track<K>(&self, asset: &AssetManifest<K>) -> Result<Option<SubTrackId>, LoadError> {
+        match &asset.track {
+            Some(track) => Ok(Some(*self.tracks.get(track).ok_or_else(|| {
+                LoadError::InvalidManifest(format!("unknown track \"{}\"", track))
+            })?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<K: PartialEq> Manifest<K> {
+    pub fn asset(&self, name: &K) -> Option<&AssetManifest<K>> {
+        self.assets.iter().find(|asset| asset.name == *name)
+    }
+
+    /// Checks that asset `name` lasts at least its semantic duration, if
+    /// it has one, and no longer if it has an exact length. Returns a
+    /// description of the mismatch otherwise.
+    pub fn check_length(&self, name: &K, sample_rate: u32, length: usize) -> Option<String> {
+        let asset = self.asset(name)?;
+        let beats = asset.semantic_duration?;
+        let expected = Tempo(self.tempo).beats_to_seconds(beats);
+        let actual = length as f64 / sample_rate as f64;
+        let too_short = actual < expected - LENGTH_TOLERANCE;
+        let too_long = asset.exact_length && actual > expected + LENGTH_TOLERANCE;
+        if !too_short && !too_long {
+            return None;
+        }
+        Some(format!(
+            "{} lasts {:.3} s, but {} beats at {} BPM last {:.3} s ({:+.1} ms)",
+            asset.label(),
+            actual,
+            beats,
+            self.tempo,
+            expected,
+            (actual - expected) * 1000.0
+        ))
+    }
+
+    /// Adds the sound (and arrangement, for loops) described by the
+    /// manifest entry of asset `name` to `manager`.
+    pub fn build(
+        &self,
+        name: &K,
+        sample_rate: u32,
+        frames: Vec<Frame>,
+        manager: &mut AudioManager,
+        bindings: &Bindings,
+    ) -> Result<AssetHandle, LoadError> {
+        let asset = self.existing_asset(name)?;
+        let track = bindings.resolve_track(asset)?;
+        let mut groups = GroupSet::new();
+        for group in &asset.groups {
+            groups = groups.add(*bindings.groups.get(group).ok_or_else(|| {
+                LoadError::InvalidManifest(format!("unknown group \"{}\"", group))
+            })?);
+        }
+
+        let mut sound_settings = SoundSettings::new();
+        if let Some(beats) = asset.semantic_duration {
+            sound_settings =
+                sound_settings.semantic_duration(Tempo(self.tempo).beats_to_seconds(beats));
+        }
+
+        match asset.kind {
+            AssetKind::Sound => {
+                if let Some(track) = track {
+                    sound_settings = sound_settings.default_track(track);
+                }
+                manager
+                    .add_sound(Sound::from_frames(
+                        sample_rate,
+                        frames,
+                        sound_settings.groups(groups),
+                    ))
+                    .map(AssetHandle::Sound)
+                    .map_err(|error| LoadError::Rejected(error.to_string()))
+            }
+            AssetKind::Loop => {
+                let sound = manager
+                    .add_sound(Sound::from_frames(sample_rate, frames, sound_settings))
+                    .map_err(|error| LoadError::Rejected(error.to_string()))?;
+                let mut arrangement_settings = LoopArrangementSettings::new().groups(groups);
+                if let Some(track) = track {
+                    arrangement_settings = arrangement_settings.default_track(track);
+                }
+                match manager.add_arrangement(Arrangement::new_loop(&sound, arrangement_settings)) {
+                    Ok(arrangement) => Ok(AssetHandle::Loop(arrangement, sound)),
+                    Err(error) => {
+                        manager.remove_sound(sound.id()).ok();
+                        Err(LoadError::Rejected(error.to_string()))
+                    }
+                }
+            }
+            #[cfg(feature = "vorbis")]
+            AssetKind::Stream => Err(LoadError::InvalidManifest(format!(
+                "{} is streamed and has no decoded audio",
+                asset.label()
+            ))),
+        }
+    }
+
+    /// Adds the stream of asset `name` to `manager`, on the track the
+    /// manifest gives, which starts playing it.
+    #[cfg(feature = "vorbis")]
+    pub fn build_stream(
+        &self,
+        name: &K,
+        sound: StreamingSound,
+        manager: &mut AudioManager,
+        bindings: &Bindings,
+    ) -> Result<AssetHandle, LoadError> {
+        let asset = self.existing_asset(name)?;
+        let track = bindings
+            .resolve_track(asset)?
+            .map_or(TrackIndex::Main, TrackIndex::Sub);
+        manager
+            .add_stream(sound, track)
+            .map(AssetHandle::Stream)
+            .map_err(|error| LoadError::Rejected(error.to_string()))
+    }
+
+    fn existing_asset(&self, name: &K) -> Result<&AssetManifest<K>, LoadError> {
+        self.asset(name)
+            .ok_or_else(|| LoadError::InvalidManifest("missing asset".into()))
+    }
+}
+
+/// Loads a manifest, retrying and timing out as configured by
+/// `settings`. Conversion settings do not apply to manifests.
+pub fn load_manifest<K: DeserializeOwned + 'static>(
+    url: &'static str,
+    settings: LoadSettings,
+    callback: impl FnOnce(Result<Manifest<K>, LoadError>) + 'static,
+) -> LoadTask {
+    let task = LoadTask::new();
+    let signal = task.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = load_manifest_async(url, settings, Some(&signal)).await;
+        if !signal.aborted() {
+            callback(result)
+        }
+    });
+    task
+}
+
+pub async fn load_manifest_async<K: DeserializeOwned>(
+    url: &str,
+    settings: LoadSettings,
+    signal: Option<&AbortSignal>,
+) -> Result<Manifest<K>, LoadError> {
+    let body = super::download(url, "application/json", settings, signal, |_| {}).await?;
+    let mut manifest: Manifest<K> = serde_json::from_slice(&body)
+        .map_err(|error| LoadError::InvalidManifest(error.to_string()))?;
+    manifest.hash = super::persistent_cache::hash(body);
+    Ok(manifest)
+}
+
+/// Measures how much the assets listed by the manifest at `url` weigh,
+/// in bytes, counting the first encoding of each asset this browser can
+/// decode. Only the headers of the files are requested. Gives `None` if
+/// the size of an asset is unknown.
+pub fn load_download_size(
+    url: &'static str,
+    callback: impl FnOnce(Option<u64>) + 'static,
+) -> LoadTask {
+    let task = LoadTask::new();
+    let signal = task.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        let size = download_size_async(url, &signal).await;
+        if !signal.aborted() {
+            callback(size)
+        }
+    });
+    task
+}
+
+async fn download_size_async(url: &str, signal: &AbortSignal) -> Option<u64> {
+    let manifest: Manifest<String> = load_manifest_async(url, LoadSettings::new(), Some(signal))
+        .await
+        .ok()?;
+    let mut total = 0;
+    for asset in &manifest.assets {
+        let source = asset
+            .sources
+            .iter()
+            .find(|source| source.format.is_supported())?;
+        let resp = super::fetch("HEAD", &source.url, source.format.mime_type(), Some(signal))
+            .await
+            .ok()?;
+        let length: u64 = resp.headers().get("Content-Length").ok()??.parse().ok()?;
+        total += length;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stem with a tail and a loop of exact length, both four beats
+    /// long at 120 BPM, which is two seconds.
+    fn manifest() -> Manifest<String> {
+        serde_json::from_str(
+            r#"{
+                "tempo": 120.0,
+                "assets": [
+                    {
+                        "name": "stem",
+                        "sources": ["/stem.ogg"],
+                        "semantic-duration": 4.0
+                    },
+                    {
+                        "name": "loop",
+                        "sources": ["/loop.ogg"],
+                        "semantic-duration": 4.0,
+                        "exact-length": true
+                    },
+                    {
+                        "name": "fill",
+                        "sources": ["/fill.ogg"]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tails_are_allowed() {
+        let manifest = manifest();
+        assert_eq!(manifest.check_length(&"stem".into(), 48000, 96000), None);
+        assert_eq!(manifest.check_length(&"stem".into(), 48000, 120000), None);
+        assert!(manifest
+            .check_length(&"stem".into(), 48000, 95000)
+            .is_some());
+    }
+
+    #[test]
+    fn exact_lengths_are_enforced_both_ways() {
+        let manifest = manifest();
+        assert_eq!(manifest.check_length(&"loop".into(), 44100, 88200), None);
+        // Within the tolerance.
+        assert_eq!(manifest.check_length(&"loop".into(), 44100, 88201), None);
+        assert!(manifest
+            .check_length(&"loop".into(), 44100, 88300)
+            .is_some());
+        assert!(manifest
+            .check_length(&"loop".into(), 44100, 88100)
+            .is_some());
+    }
+
+    #[test]
+    fn assets_without_semantic_duration_are_not_checked() {
+        let manifest = manifest();
+        assert_eq!(manifest.check_length(&"fill".into(), 44100, 1), None);
+        assert_eq!(manifest.check_length(&"missing".into(), 44100, 1), None);
+    }
+}
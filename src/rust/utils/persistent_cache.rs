@@ -0,0 +1,140 @@
+use super::channels::{deinterleave, interleave};
+use js_sys::{Float32Array, Object, Promise, Reflect};
+use kira::Frame;
+use std::cell::RefCell;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode};
+
+const DATABASE_NAME: &str = "kira-web-demo";
+const DATABASE_VERSION: u32 = 1;
+const STORE_NAME: &str = "decoded-audio";
+
+thread_local! {
+    static DATABASE: RefCell<Option<IdbDatabase>> = RefCell::new(None);
+}
+
+/// A 64-bit FNV-1a hash, used to detect changed manifests and corrupted
+/// records.
+pub fn hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn content_hash(samples: &[f32]) -> String {
+    format!(
+        "{:016x}",
+        hash(samples.iter().flat_map(|sample| {
+            let bytes = sample.to_bits().to_le_bytes();
+            (0..bytes.len()).map(move |i| bytes[i])
+        }))
+    )
+}
+
+/// Returns the audio data stored for `url` by a previous page load, if
+/// it was stored with the same `version`. Storage errors (for instance
+/// in private browsing) count as a cache miss.
+pub async fn get(url: &str, version: u64) -> Option<(u32, Vec<Frame>)> {
+    read(url, version).await.ok().flatten()
+}
+
+/// Stores the audio data decoded from `url` in the background. Records
+/// stored under another version are replaced.
+pub fn insert(url: &str, version: u64, sample_rate: u32, frames: &[Frame]) {
+    let samples = interleave(frames);
+    let record = Object::new();
+    let fields: [(&str, JsValue); 4] = [
+        ("sampleRate", sample_rate.into()),
+        ("frames", Float32Array::from(&samples[..]).into()),
+        ("contentHash", content_hash(&samples).into()),
+        ("manifestHash", format!("{:016x}", version).into()),
+    ];
+    for (name, value) in &fields {
+        Reflect::set(&record, &(*name).into(), value).ok();
+    }
+
+    let url = url.to_string();
+    wasm_bindgen_futures::spawn_local(async move {
+        write(&url, &record).await.ok();
+    });
+}
+
+async fn read(url: &str, version: u64) -> Result<Option<(u32, Vec<Frame>)>, JsValue> {
+    let store = database()
+        .await?
+        .transaction_with_str(STORE_NAME)?
+        .object_store(STORE_NAME)?;
+    let record = wait_for(&store.get(&url.into())?).await?;
+    if record.is_undefined() {
+        return Ok(None);
+    }
+
+    let manifest_hash = Reflect::get(&record, &"manifestHash".into())?.as_string();
+    if manifest_hash != Some(format!("{:016x}", version)) {
+        return Ok(None);
+    }
+
+    let sample_rate = Reflect::get(&record, &"sampleRate".into())?
+        .as_f64()
+        .ok_or_else(|| JsValue::from("invalid sample rate"))?;
+    let samples = Reflect::get(&record, &"frames".into())?
+        .dyn_into::<Float32Array>()?
+        .to_vec();
+    let stored_hash = Reflect::get(&record, &"contentHash".into())?.as_string();
+    if stored_hash != Some(content_hash(&samples)) {
+        return Ok(None);
+    }
+
+    Ok(Some((sample_rate as u32, deinterleave(&samples))))
+}
+
+async fn write(url: &str, record: &JsValue) -> Result<(), JsValue> {
+    let store = database()
+        .await?
+        .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+        .object_store(STORE_NAME)?;
+    wait_for(&store.put_with_key(record, &url.into())?).await?;
+    Ok(())
+}
+
+/// Returns the database the cache is stored in, opening it (and
+/// creating it if needed) on first use.
+async fn database() -> Result<IdbDatabase, JsValue> {
+    if let Some(database) = DATABASE.with(|database| database.borrow().clone()) {
+        return Ok(database);
+    }
+
+    // Workers open their own connection to the same database.
+    let factory = super::Scope::current()
+        .map_err(|error| JsValue::from(error.to_string()))?
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from("IndexedDB is not available"))?;
+    let request = factory.open_with_u32(DATABASE_NAME, DATABASE_VERSION)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade_needed: Closure<dyn FnMut()> = Closure::once(move || {
+        if let Ok(database) = upgrade_request
+            .result()
+            .and_then(|database| database.dyn_into::<IdbDatabase>())
+        {
+            database.create_object_store(STORE_NAME).ok();
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade_needed.as_ref().unchecked_ref()));
+    let database: IdbDatabase = wait_for(&request).await?.dyn_into()?;
+    request.set_onupgradeneeded(None);
+
+    DATABASE.with(|cached| *cached.borrow_mut() = Some(database.clone()));
+    Ok(database)
+}
+
+/// Waits for `request` to complete and returns its result.
+async fn wait_for(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let completion = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    JsFuture::from(completion).await?;
+    request.result()
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use yew::{html, Html};
+
+/// How much of an asset has been downloaded so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LoadProgress {
+    pub received: u64,
+    /// The size announced by the `Content-Length` header, if any.
+    pub total: Option<u64>,
+    /// How many times the download was retried, 0 for the first try.
+    pub attempt: u32,
+}
+
+impl LoadProgress {
+    /// The progress of an asset that did not need downloading.
+    pub fn done() -> Self {
+        LoadProgress {
+            received: 0,
+            total: Some(0),
+            attempt: 0,
+        }
+    }
+
+    pub fn fraction(self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.received as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+/// Keeps track of the download progress of every asset of a demo.
+#[derive(Debug, Clone, Default)]
+pub struct AssetProgress {
+    /// The progress of every asset, under the label shown for it.
+    assets: Vec<(String, LoadProgress)>,
+}
+
+impl AssetProgress {
+    pub fn new(labels: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            assets: labels
+                .into_iter()
+                .map(|label| (label, LoadProgress::default()))
+                .collect(),
+        }
+    }
+
+    pub fn update(&mut self, label: &str, progress: LoadProgress) {
+        match self.assets.iter_mut().find(|(asset, _)| *asset == label) {
+            Some((_, current)) => *current = progress,
+            None => self.assets.push((label.to_string(), progress)),
+        }
+    }
+
+    /// The combined progress of all assets. The total is only known
+    /// once every asset has announced its size.
+    pub fn total(&self) -> LoadProgress {
+        self.assets.iter().fold(
+            LoadProgress {
+                received: 0,
+                total: Some(0),
+                attempt: 0,
+            },
+            |sum, (_, progress)| LoadProgress {
+                received: sum.received + progress.received,
+                total: sum.total.and_then(|sum| Some(sum + progress.total?)),
+                attempt: 0,
+            },
+        )
+    }
+
+    pub fn view(&self) -> Html {
+        html! {
+            <div class="progress-list centered">
+                { for self.assets.iter().map(|(label, progress)| progress_row(label, *progress)) }
+                { progress_row("Total", self.total()) }
+            </div>
+        }
+    }
+}
+
+fn progress_row(label: &str, progress: LoadProgress) -> Html {
+    let mut text = match progress.total {
+        Some(total) => format!("{} / {} KB", progress.received / 1024, total / 1024),
+        None => format!("{} KB", progress.received / 1024),
+    };
+    if progress.attempt > 0 {
+        text += &format!(" (retry {})", progress.attempt);
+    }
+    html! {
+        <div class="progress-row">
+            <div class="progress-label">{ label }</div>
+            <div class="progress-bar">
+                <div
+                    class="progress-bar-fill"
+                    style=format!("width: {}%", progress.fraction().unwrap_or(0.0) * 100.0)
+                />
+            </div>
+            <div class="progress-text">{ text }</div>
+        </div>
+    }
+}
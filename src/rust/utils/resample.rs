@@ -0,0 +1,145 @@
+use kira::Frame;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Number of input samples on each side of the output position that
+/// the windowed-sinc resampler looks at when upsampling.
+const SINC_HALF_WIDTH: f64 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResampleQuality {
+    /// Linear interpolation between neighbouring frames. Cheap, but
+    /// lets some aliasing and high frequency loss through.
+    Linear,
+    /// Blackman-windowed sinc interpolation, low-pass filtered below
+    /// the lower of the two Nyquist frequencies.
+    Sinc,
+}
+
+/// Converts `frames` from the `from` sample rate to the `to` sample rate.
+pub fn resample(frames: &[Frame], from: u32, to: u32, quality: ResampleQuality) -> Vec<Frame> {
+    if from == to || frames.is_empty() {
+        return frames.to_vec();
+    }
+
+    let ratio = from as f64 / to as f64;
+    let length = (frames.len() as u64 * to as u64 + from as u64 - 1) / from as u64;
+    let positions = (0..length as usize).map(|i| i as f64 * ratio);
+
+    match quality {
+        ResampleQuality::Linear => positions
+            .map(|position| {
+                let index = position as usize;
+                let fraction = (position - index as f64) as f32;
+                let current = frames[index];
+                let next = frames.get(index + 1).copied().unwrap_or(current);
+                Frame {
+                    left: current.left + (next.left - current.left) * fraction,
+                    right: current.right + (next.right - current.right) * fraction,
+                }
+            })
+            .collect(),
+        ResampleQuality::Sinc => {
+            // When downsampling, the kernel is widened so that it also
+            // filters out everything above the new Nyquist frequency.
+            let cutoff = (1.0 / ratio).min(1.0);
+            let half_width = SINC_HALF_WIDTH / cutoff;
+            positions
+                .map(|position| {
+                    let first = (position - half_width).ceil().max(0.0) as usize;
+                    let last = ((position + half_width).floor() as usize).min(frames.len() - 1);
+
+                    let (mut left, mut right, mut total_weight) = (0.0, 0.0, 0.0);
+                    for (index, frame) in frames.iter().enumerate().take(last + 1).skip(first) {
+                        let offset = position - index as f64;
+                        let weight = sinc(cutoff * offset) * blackman(offset / half_width);
+                        left += frame.left as f64 * weight;
+                        right += frame.right as f64 * weight;
+                        total_weight += weight;
+                    }
+
+                    Frame {
+                        left: (left / total_weight) as f32,
+                        right: (right / total_weight) as f32,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The Blackman window, for `t` in `-1.0..=1.0`.
+fn blackman(t: f64) -> f64 {
+    0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUALITIES: [ResampleQuality; 2] = [ResampleQuality::Linear, ResampleQuality::Sinc];
+
+    fn dc(length: usize, level: f32) -> Vec<Frame> {
+        vec![
+            Frame {
+                left: level,
+                right: -level,
+            };
+            length
+        ]
+    }
+
+    #[test]
+    fn length_follows_the_ratio() {
+        for &quality in &QUALITIES {
+            assert_eq!(
+                resample(&dc(44100, 0.5), 44100, 48000, quality).len(),
+                48000
+            );
+            assert_eq!(
+                resample(&dc(48000, 0.5), 48000, 44100, quality).len(),
+                44100
+            );
+            // Partial output frames are rounded up.
+            assert_eq!(resample(&dc(100, 0.5), 44100, 48000, quality).len(), 109);
+        }
+    }
+
+    #[test]
+    fn dc_keeps_its_level() {
+        for &quality in &QUALITIES {
+            for &(from, to) in &[(44100, 48000), (48000, 44100)] {
+                for frame in resample(&dc(4800, 0.5), from, to, quality) {
+                    assert!((frame.left - 0.5).abs() < 1e-4, "{:?}", quality);
+                    assert!((frame.right + 0.5).abs() < 1e-4, "{:?}", quality);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_rate_is_a_no_op() {
+        let frames: Vec<Frame> = (0..64)
+            .map(|i| Frame {
+                left: (i as f32 * 0.3).sin(),
+                right: (i as f32 * 0.7).cos(),
+            })
+            .collect();
+        for &quality in &QUALITIES {
+            let resampled = resample(&frames, 48000, 48000, quality);
+            assert_eq!(resampled.len(), frames.len());
+            for (resampled, frame) in resampled.iter().zip(&frames) {
+                assert_eq!(resampled.left, frame.left);
+                assert_eq!(resampled.right, frame.right);
+            }
+        }
+    }
+}
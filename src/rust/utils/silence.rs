@@ -0,0 +1,94 @@
+use kira::Frame;
+
+/// Samples quieter than this (-60 dBFS) count as silence.
+const SILENCE_THRESHOLD: f32 = 0.001;
+/// The longest leading silence that is trimmed, in seconds. Encoder
+/// padding is much shorter than this, while longer silences are more
+/// likely to be part of the music.
+const MAX_TRIM_DURATION: f64 = 0.05;
+
+/// Measures the silence encoders add to the start of files, and returns
+/// how many frames of it should be trimmed from `frames`.
+pub fn leading_silence(sample_rate: u32, frames: &[Frame]) -> usize {
+    let max_trimmed = (MAX_TRIM_DURATION * sample_rate as f64) as usize;
+    let silent = frames
+        .iter()
+        .take(max_trimmed + 1)
+        .take_while(|frame| {
+            frame.left.abs() < SILENCE_THRESHOLD && frame.right.abs() < SILENCE_THRESHOLD
+        })
+        .count();
+    if silent > max_trimmed || silent == frames.len() {
+        0
+    } else {
+        silent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 1000;
+    /// `MAX_TRIM_DURATION` at `SAMPLE_RATE`.
+    const MAX_TRIMMED: usize = 50;
+
+    fn frames(silent: usize, sound: usize) -> Vec<Frame> {
+        let mut frames = vec![
+            Frame {
+                left: 0.0001,
+                right: -0.0001,
+            };
+            silent
+        ];
+        frames.extend(vec![
+            Frame {
+                left: 0.5,
+                right: -0.5,
+            };
+            sound
+        ]);
+        frames
+    }
+
+    #[test]
+    fn short_silence_is_trimmed() {
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames(10, 100)), 10);
+        assert_eq!(
+            leading_silence(SAMPLE_RATE, &frames(MAX_TRIMMED, 100)),
+            MAX_TRIMMED
+        );
+    }
+
+    #[test]
+    fn sound_in_either_channel_ends_the_silence() {
+        let mut frames = frames(10, 100);
+        frames[5].right = 0.01;
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames), 5);
+    }
+
+    #[test]
+    fn long_silence_is_kept() {
+        assert_eq!(
+            leading_silence(SAMPLE_RATE, &frames(MAX_TRIMMED + 1, 100)),
+            0
+        );
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames(1000, 100)), 0);
+    }
+
+    #[test]
+    fn silent_audio_is_kept() {
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames(10, 0)), 0);
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames(1000, 0)), 0);
+    }
+
+    #[test]
+    fn empty_audio_is_kept() {
+        assert_eq!(leading_silence(SAMPLE_RATE, &[]), 0);
+    }
+
+    #[test]
+    fn audio_starting_with_sound_is_kept() {
+        assert_eq!(leading_silence(SAMPLE_RATE, &frames(0, 100)), 0);
+    }
+}
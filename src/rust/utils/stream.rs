@@ -0,0 +1,168 @@
+use super::{
+    check_aborted, fetch, sleep,
+    vorbis::{OggPackets, PacketDecoder},
+    AudioFormat, AudioSource, BodyChunks, LoadError, LoadTask,
+};
+use kira::{audio_stream::AudioStream, Frame};
+use ringbuf::{Consumer, RingBuffer};
+use std::time::Duration;
+use web_sys::AbortSignal;
+
+/// How many seconds of decoded audio are kept ready ahead of playback.
+const BUFFER_DURATION: f64 = 4.0;
+
+/// A sound that plays audio as it is being downloaded and decoded.
+/// Add it to the audio manager with `AudioManager::add_stream`.
+///
+/// If decoding falls behind playback, the sound plays silence until
+/// it catches up. Once the whole file has been played, it keeps
+/// playing silence until it is removed.
+pub struct StreamingSound {
+    consumer: Consumer<Frame>,
+    sample_rate: u32,
+    current: Frame,
+    upcoming: Frame,
+    /// The position between the current and upcoming frames, from 0
+    /// to 1.
+    fraction: f64,
+}
+
+impl StreamingSound {
+    fn new(consumer: Consumer<Frame>, sample_rate: u32) -> Self {
+        let silence = Frame {
+            left: 0.0,
+            right: 0.0,
+        };
+        Self {
+            consumer,
+            sample_rate,
+            current: silence,
+            upcoming: silence,
+            fraction: 0.0,
+        }
+    }
+}
+
+impl AudioStream for StreamingSound {
+    fn next(&mut self, dt: f64) -> Frame {
+        // The decoded audio keeps the file's sample rate, so it is
+        // linearly interpolated to the rate kira asks for.
+        self.fraction += dt * self.sample_rate as f64;
+        while self.fraction >= 1.0 {
+            self.fraction -= 1.0;
+            self.current = self.upcoming;
+            self.upcoming = self.consumer.pop().unwrap_or(Frame {
+                left: 0.0,
+                right: 0.0,
+            });
+        }
+        let fraction = self.fraction as f32;
+        Frame {
+            left: self.current.left + (self.upcoming.left - self.current.left) * fraction,
+            right: self.current.right + (self.upcoming.right - self.current.right) * fraction,
+        }
+    }
+}
+
+/// Starts streaming the first Ogg Vorbis file of `sources`, which
+/// must be available in that format.
+///
+/// `callback` receives the sound as soon as the first chunk of audio
+/// is decoded. The rest of the file keeps downloading and decoding in
+/// the background for as long as the returned task is kept alive.
+/// Streams are not retried: if the download fails after the sound was
+/// handed out, the sound simply falls silent.
+pub fn stream_audio(
+    sources: Vec<AudioSource>,
+    callback: impl FnOnce(Result<StreamingSound, LoadError>) + 'static,
+) -> LoadTask {
+    let task = LoadTask::new();
+    let signal = task.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        let mut callback = Some(callback);
+        let result = match sources
+            .iter()
+            .find(|source| source.format == AudioFormat::Ogg)
+        {
+            Some(source) => {
+                stream(source, &signal, |sound| {
+                    if let Some(callback) = callback.take() {
+                        if !signal.aborted() {
+                            callback(Ok(sound))
+                        }
+                    }
+                })
+                .await
+            }
+            None => Err(LoadError::UnsupportedFormat),
+        };
+        if let (Err(error), Some(callback)) = (result, callback) {
+            if !signal.aborted() {
+                callback(Err(error))
+            }
+        }
+    });
+    task
+}
+
+async fn stream(
+    source: &AudioSource,
+    signal: &AbortSignal,
+    on_start: impl FnOnce(StreamingSound),
+) -> Result<(), LoadError> {
+    let resp = fetch("GET", &source.url, source.format.mime_type(), Some(signal)).await?;
+    let mut chunks = BodyChunks::new(&resp)?;
+    let mut packets = OggPackets::default();
+
+    let mut decoder = loop {
+        if let Some(decoder) = PacketDecoder::from_headers(&mut packets)? {
+            break decoder;
+        }
+        match chunks.next().await? {
+            Some(chunk) => packets.push(&chunk)?,
+            None => return Err(LoadError::Undecodable("missing Vorbis headers".into())),
+        }
+    };
+
+    let capacity = (BUFFER_DURATION * decoder.sample_rate() as f64) as usize;
+    let (mut producer, consumer) = RingBuffer::new(capacity).split();
+    let mut start = Some((on_start, consumer));
+    // Decoded frames that did not fit in the ring buffer yet
+    let mut pending = Vec::new();
+    let mut body_done = false;
+    loop {
+        check_aborted(Some(signal))?;
+
+        loop {
+            let pushed = producer.push_slice(&pending);
+            pending.drain(..pushed);
+            if !pending.is_empty() {
+                break;
+            }
+            match packets.pop() {
+                Some(packet) => pending = decoder.decode(&packet)?,
+                None => break,
+            }
+        }
+
+        if !producer.is_empty() || body_done {
+            if let Some((on_start, consumer)) = start.take() {
+                on_start(StreamingSound::new(consumer, decoder.sample_rate()));
+            }
+        }
+
+        if !body_done {
+            // Keep downloading even when the ring buffer is full, the
+            // encoded audio is much smaller than the decoded one.
+            match chunks.next().await? {
+                Some(chunk) => packets.push(&chunk)?,
+                None => body_done = true,
+            }
+        } else if pending.is_empty() && packets.is_empty() {
+            return Ok(());
+        } else {
+            // Wait for playback to make room in the ring buffer
+            sleep(Duration::from_secs_f64(BUFFER_DURATION / 4.0)).await?;
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::AudioSession;
+use kira::{instance::StopInstanceSettings, manager::AudioManager, parameter::tween::Tween};
+use std::cell::{Cell, RefCell};
+use wasm_bindgen::JsCast;
+
+/// How long instances take to fade out when a demo is left, in seconds.
+const FADE_OUT_DURATION: f64 = 0.25;
+
+thread_local! {
+    /// The end of the teardown of the last demo that was left, if its
+    /// instances are still fading out, and the number of that teardown.
+    static PENDING: RefCell<Option<(u64, Box<dyn FnOnce()>)>> = RefCell::new(None);
+    static TEARDOWN_COUNT: Cell<u64> = Cell::new(0);
+}
+
+/// The settings to stop a demo's instances with when it is left.
+pub fn fade_out() -> StopInstanceSettings {
+    StopInstanceSettings::new().fade_tween(Tween::linear(FADE_OUT_DURATION))
+}
+
+/// Finishes tearing down a demo whose instances were stopped with
+/// [`fade_out`]: once they are silent, or as soon as another session
+/// starts, `unload` removes every resource the demo added to the
+/// manager and returns the size of the PCM data it released, which is
+/// logged to the console. The manager frees the removed resources when
+/// the next session starts.
+pub fn teardown(
+    demo: &'static str,
+    session: &AudioSession,
+    unload: impl FnOnce(&mut AudioManager) -> usize + 'static,
+) {
+    finish_pending();
+    let manager = session.shared();
+    let finish = move || {
+        let mut manager = manager.borrow_mut();
+        let released = unload(&mut manager);
+        web_sys::console::info_1(
+            &format!(
+                "left the {}, unloaded {:.1} MB of audio data",
+                demo,
+                released as f64 / 1_000_000.0
+            )
+            .into(),
+        );
+    };
+    let id = TEARDOWN_COUNT.with(|count| {
+        count.set(count.get() + 1);
+        count.get()
+    });
+    PENDING.with(|pending| *pending.borrow_mut() = Some((id, Box::new(finish))));
+
+    // If the timer cannot be set, the teardown finishes when the next
+    // session starts.
+    if let Some(window) = web_sys::window() {
+        let callback = wasm_bindgen::closure::Closure::once_into_js(move || {
+            // The teardown may have been finished early, and another one
+            // started since.
+            let is_current = PENDING.with(
+                |pending| matches!(&*pending.borrow(), Some((pending_id, _)) if *pending_id == id),
+            );
+            if is_current {
+                finish_pending();
+            }
+        });
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                callback.unchecked_ref(),
+                (FADE_OUT_DURATION * 1000.0).ceil() as i32,
+            )
+            .ok();
+    }
+}
+
+/// Runs the end of the pending teardown, if any.
+pub(super) fn finish_pending() {
+    let pending = PENDING.with(|pending| pending.borrow_mut().take());
+    if let Some((_, finish)) = pending {
+        finish();
+    }
+}
@@ -0,0 +1,205 @@
+use super::{channels::ChannelLayout, LoadError};
+use kira::Frame;
+use lewton::{
+    audio::{read_audio_packet_generic, PreviousWindowRight},
+    header::{read_header_comment, read_header_ident, read_header_setup, IdentHeader, SetupHeader},
+    inside_ogg::OggStreamReader,
+};
+use std::{collections::VecDeque, io::Cursor};
+
+/// Vorbis orders 5.1 channels as left, center, right, surround left,
+/// surround right, LFE. These are the indices of the Vorbis channels
+/// in the order `ChannelLayout` expects.
+const FIVE_POINT_ONE_ORDER: [usize; 6] = [0, 2, 1, 5, 3, 4];
+
+/// Decodes a complete Ogg Vorbis file without going through the browser.
+pub fn decode(bytes: &[u8]) -> Result<(u32, Vec<Frame>), LoadError> {
+    let mut reader = OggStreamReader::new(Cursor::new(bytes))
+        .map_err(|error| LoadError::Undecodable(error.to_string()))?;
+    let layout = ChannelLayout::from_channel_count(reader.ident_hdr.audio_channels as u32)?;
+
+    let mut channels = vec![Vec::new(); layout.channel_count()];
+    while let Some(packet) = reader
+        .read_dec_packet_generic::<Vec<Vec<f32>>>()
+        .map_err(|error| LoadError::Undecodable(error.to_string()))?
+    {
+        for (channel, samples) in channels.iter_mut().zip(packet) {
+            channel.extend(samples);
+        }
+    }
+
+    Ok((
+        reader.ident_hdr.audio_sample_rate,
+        to_frames(layout, channels),
+    ))
+}
+
+fn to_frames(layout: ChannelLayout, mut channels: Vec<Vec<f32>>) -> Vec<Frame> {
+    if layout == ChannelLayout::FivePointOne {
+        channels = FIVE_POINT_ONE_ORDER
+            .iter()
+            .map(|&index| std::mem::take(&mut channels[index]))
+            .collect();
+    }
+    layout.to_frames(&channels)
+}
+
+/// Splits an Ogg stream into packets as its bytes arrive. Pages that
+/// are not complete yet are kept until the rest of them is pushed.
+/// Only streams with a single logical bitstream are supported.
+#[derive(Debug, Default)]
+pub struct OggPackets {
+    buffer: Vec<u8>,
+    /// The start of a packet that continues on the next page.
+    partial_packet: Vec<u8>,
+    packets: VecDeque<Vec<u8>>,
+}
+
+impl OggPackets {
+    pub fn push(&mut self, bytes: &[u8]) -> Result<(), LoadError> {
+        self.buffer.extend_from_slice(bytes);
+        let mut start = 0;
+        while let Some(length) = page_length(&self.buffer[start..])? {
+            let page = &self.buffer[start..start + length];
+            let segment_count = page[26] as usize;
+            let mut offset = 27 + segment_count;
+            for &segment_length in &page[27..27 + segment_count] {
+                let segment_length = segment_length as usize;
+                self.partial_packet
+                    .extend_from_slice(&page[offset..offset + segment_length]);
+                offset += segment_length;
+                // A segment shorter than 255 bytes ends its packet
+                if segment_length < 255 {
+                    self.packets
+                        .push_back(std::mem::take(&mut self.partial_packet));
+                }
+            }
+            start += length;
+        }
+        self.buffer.drain(..start);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.packets.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+}
+
+/// The length of the page `bytes` starts with, or `None` if the page
+/// is not complete yet.
+fn page_length(bytes: &[u8]) -> Result<Option<usize>, LoadError> {
+    if bytes.len() < 27 {
+        return Ok(None);
+    }
+    if &bytes[..4] != b"OggS" {
+        return Err(LoadError::Undecodable("invalid Ogg page".into()));
+    }
+    let segment_count = bytes[26] as usize;
+    if bytes.len() < 27 + segment_count {
+        return Ok(None);
+    }
+    let length = 27
+        + segment_count
+        + bytes[27..27 + segment_count]
+            .iter()
+            .map(|&segment_length| segment_length as usize)
+            .sum::<usize>();
+    Ok(if bytes.len() >= length {
+        Some(length)
+    } else {
+        None
+    })
+}
+
+/// Decodes a Vorbis stream one packet at a time.
+pub struct PacketDecoder {
+    ident: IdentHeader,
+    setup: SetupHeader,
+    layout: ChannelLayout,
+    previous_window: PreviousWindowRight,
+}
+
+impl PacketDecoder {
+    /// Reads the three header packets of the stream, or returns `None`
+    /// if they have not all arrived yet.
+    pub fn from_headers(packets: &mut OggPackets) -> Result<Option<Self>, LoadError> {
+        if packets.len() < 3 {
+            return Ok(None);
+        }
+        let undecodable =
+            |error: lewton::header::HeaderReadError| LoadError::Undecodable(error.to_string());
+        let ident = read_header_ident(&packets.pop().unwrap()).map_err(undecodable)?;
+        read_header_comment(&packets.pop().unwrap()).map_err(undecodable)?;
+        let setup = read_header_setup(
+            &packets.pop().unwrap(),
+            ident.audio_channels,
+            (ident.blocksize_0, ident.blocksize_1),
+        )
+        .map_err(undecodable)?;
+        let layout = ChannelLayout::from_channel_count(ident.audio_channels as u32)?;
+        Ok(Some(Self {
+            ident,
+            setup,
+            layout,
+            previous_window: PreviousWindowRight::new(),
+        }))
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.ident.audio_sample_rate
+    }
+
+    pub fn decode(&mut self, packet: &[u8]) -> Result<Vec<Frame>, LoadError> {
+        let channels = read_audio_packet_generic::<Vec<Vec<f32>>>(
+            &self.ident,
+            &self.setup,
+            packet,
+            &mut self.previous_window,
+        )
+        .map_err(|error| LoadError::Undecodable(error.to_string()))?;
+        Ok(to_frames(self.layout, channels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two beats at 128 BPM, in stereo at 44.1 kHz.
+    const FIXTURE: &[u8] = include_bytes!("../../../static/drum-fill-demo/2-beat-fill.ogg");
+
+    #[test]
+    fn decode_reads_sample_rate_and_length() {
+        let (sample_rate, frames) = decode(FIXTURE).unwrap();
+        assert_eq!(sample_rate, 44100);
+        // The granule position of the last page, which the last packet
+        // is cut to.
+        assert_eq!(frames.len(), 41344);
+    }
+
+    #[test]
+    fn five_point_one_is_reordered() {
+        // Left, center, right, surround left, surround right, LFE.
+        let vorbis_channels = [1.0, 10.0, 100.0, 1000.0, 10000.0, 100000.0]
+            .iter()
+            .map(|&sample| vec![sample])
+            .collect();
+        let frames = to_frames(ChannelLayout::FivePointOne, vorbis_channels);
+
+        let expected =
+            ChannelLayout::FivePointOne.downmix(&[1.0, 100.0, 10.0, 100000.0, 1000.0, 10000.0]);
+        assert_eq!(frames.len(), 1);
+        assert!((frames[0].left - expected.left).abs() < 1e-3);
+        assert!((frames[0].right - expected.right).abs() < 1e-3);
+        // The LFE channel does not reach either side.
+        assert!(frames[0].left < 1000.0 && frames[0].right < 10000.0);
+    }
+}
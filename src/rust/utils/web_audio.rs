@@ -0,0 +1,81 @@
+use super::{channels::ChannelLayout, LoadError};
+use js_sys::Uint8Array;
+use kira::Frame;
+use std::cell::RefCell;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AudioBuffer, AudioContext, AudioContextState, HtmlAudioElement};
+
+thread_local! {
+    static DECODING_CONTEXT: RefCell<Option<AudioContext>> = RefCell::new(None);
+}
+
+/// Returns the audio context used to decode every asset, creating it
+/// on first use. Browsers limit how many contexts a page may create,
+/// so they should not be created per request.
+pub fn decoding_context() -> Result<AudioContext, JsValue> {
+    DECODING_CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        if let Some(context) = &*context {
+            return Ok(context.clone());
+        }
+        let created = AudioContext::new()?;
+        *context = Some(created.clone());
+        Ok(created)
+    })
+}
+
+/// The sample rate the browser outputs audio at, which is also the
+/// rate the `AudioManager` renders at.
+pub fn output_sample_rate() -> Option<u32> {
+    decoding_context()
+        .ok()
+        .map(|context| context.sample_rate() as u32)
+}
+
+/// Whether the browser lets the page play audio, as seen from the
+/// decoding context. Autoplay policies keep audio contexts suspended
+/// until the user interacts with the page, after which contexts created
+/// later run right away. This says nothing about contexts created
+/// earlier, such as the one of an existing audio manager.
+pub fn is_unlocked() -> bool {
+    decoding_context().map_or(true, |context| {
+        context.state() != AudioContextState::Suspended
+    })
+}
+
+/// Resumes the decoding context, which only works while the browser
+/// handles a user gesture, such as a click. Other contexts, such as the
+/// one an audio manager outputs to, stay as they are.
+pub fn unlock() {
+    if let Ok(context) = decoding_context() {
+        context.resume().ok();
+    }
+}
+
+/// Whether the browser reports that it might be able to play audio of
+/// the given MIME type.
+pub fn can_play(mime_type: &str) -> bool {
+    HtmlAudioElement::new().map_or(false, |audio| !audio.can_play_type(mime_type).is_empty())
+}
+
+/// Decodes an encoded audio file using the browser's decoders.
+pub async fn decode(encoded: &[u8]) -> Result<(u32, Vec<Frame>), LoadError> {
+    let audio_ctx = decoding_context().map_err(LoadError::undecodable)?;
+    let decoded: AudioBuffer = JsFuture::from(
+        audio_ctx
+            .decode_audio_data(&Uint8Array::from(encoded).buffer())
+            .map_err(LoadError::undecodable)?,
+    )
+    .await
+    .and_then(|decoded| decoded.dyn_into())
+    .map_err(LoadError::undecodable)?;
+
+    let layout = ChannelLayout::from_channel_count(decoded.number_of_channels())?;
+    let channels = (0..decoded.number_of_channels())
+        .map(|channel| decoded.get_channel_data(channel))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(LoadError::undecodable)?;
+
+    Ok((decoded.sample_rate() as u32, layout.to_frames(&channels)))
+}
@@ -5,10 +5,16 @@ const CopyWebpackPlugin = require('copy-webpack-plugin');
 
 const distPath = path.resolve(__dirname, "dist");
 module.exports = {
-    entry: './src/index.js',
+    entry: {
+        index: './src/index.js',
+        decoder_worker: {
+            import: './src/decoder_worker.js',
+            chunkLoading: 'import-scripts',
+        },
+    },
     output: {
         path: distPath,
-        filename: 'index.js',
+        filename: '[name].js',
     },
     module: {
         rules: [
@@ -26,18 +32,24 @@ module.exports = {
             ],
         }),
         new HtmlWebpackPlugin({
-            title: 'Kira Web Demo'
+            title: 'Kira Web Demo',
+            chunks: ['index'],
         }),
         new HtmlWebpackPlugin({
             filename: 'underwater-demo/index.html',
-            title: 'Kira Web Demo'
+            title: 'Kira Web Demo',
+            chunks: ['index'],
         }),
         new HtmlWebpackPlugin({
             filename: 'drum-fill-demo/index.html',
-            title: 'Kira Web Demo'
+            title: 'Kira Web Demo',
+            chunks: ['index'],
         }),
         new WasmPackPlugin({
-            crateDirectory: path.resolve(__dirname, ".")
+            crateDirectory: path.resolve(__dirname, "."),
+            // Ogg Vorbis assets are then decoded in the decoder worker
+            // rather than on the page.
+            extraArgs: "-- --features vorbis",
         }),
     ]
 };